@@ -0,0 +1,396 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{OriginalUri, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::{env, fmt, fs, str::FromStr, sync::{Arc, Mutex}};
+use subtle::ConstantTimeEq;
+use tracing::{debug, warn};
+
+const MAX_SIGNED_BODY_BYTES: usize = 1024 * 1024;
+const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Read,
+    Write,
+    Delete,
+}
+
+impl Scope {
+    fn required_for(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::HEAD => Scope::Read,
+            Method::DELETE => Scope::Delete,
+            _ => Scope::Write,
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "delete" => Ok(Scope::Delete),
+            other => Err(format!("Unknown API key scope: {}", other)),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApiKey {
+    id: String,
+    secret: String,
+    scopes: HashSet<Scope>,
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("id", &self.id)
+            .field("secret", &"[redacted]")
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+/// The admin API key that authenticated the current request.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey {
+    pub id: String,
+    scopes: HashSet<Scope>,
+}
+
+impl AuthenticatedKey {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// Signatures already accepted, with their timestamps, so a captured signed
+/// request cannot be replayed while its timestamp is still in the window.
+#[derive(Debug, Default)]
+struct SeenSignatures {
+    signed_at: HashMap<Vec<u8>, i64>,
+    next_prune: i64,
+}
+
+impl SeenSignatures {
+    /// Records `signature`, returning false if it was already used.
+    fn insert(&mut self, signature: Vec<u8>, signed_at: i64, now: i64) -> bool {
+        if now >= self.next_prune {
+            self.signed_at.retain(|_, signed_at| now - *signed_at <= MAX_SIGNATURE_AGE_SECS);
+            self.next_prune = now + MAX_SIGNATURE_AGE_SECS;
+        }
+        self.signed_at.insert(signature, signed_at).is_none()
+    }
+}
+
+/// Admin API keys, each written as `id:secret:scope,scope`.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyStore {
+    keys: Arc<HashMap<String, ApiKey>>,
+    seen_signatures: Arc<Mutex<SeenSignatures>>,
+}
+
+impl ApiKeyStore {
+    /// Loads keys from `ADMIN_API_KEYS` (separated by `;`) and from the file named
+    /// by `ADMIN_API_KEYS_FILE` (one key per line, `#` starts a comment).
+    pub fn from_env() -> Result<Self, String> {
+        let mut specs = Vec::new();
+
+        if let Ok(keys) = env::var("ADMIN_API_KEYS") {
+            specs.extend(keys.split(';').map(str::to_string));
+        }
+        if let Ok(path) = env::var("ADMIN_API_KEYS_FILE") {
+            let contents = fs::read_to_string(&path)
+                .map_err(|err| format!("Failed to read {}: {}", path, err))?;
+            specs.extend(contents.lines().map(str::to_string));
+        }
+
+        Self::parse(specs.iter().map(String::as_str))
+    }
+
+    pub fn parse<'a>(specs: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+
+        for spec in specs {
+            let spec = spec.trim();
+            if spec.is_empty() || spec.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = spec.splitn(3, ':');
+            let (Some(id), Some(secret), Some(scopes)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(format!("API key entry must be id:secret:scopes, got {:?}", spec.split(':').next()));
+            };
+            if id.is_empty() || secret.is_empty() {
+                return Err(format!("API key {:?} has an empty id or secret", id));
+            }
+
+            let scopes = scopes
+                .split(',')
+                .map(Scope::from_str)
+                .collect::<Result<HashSet<_>, _>>()?;
+
+            if keys.insert(id.to_string(), ApiKey { id: id.to_string(), secret: secret.to_string(), scopes }).is_some() {
+                return Err(format!("Duplicate API key id: {}", id));
+            }
+        }
+
+        Ok(Self { keys: Arc::new(keys), seen_signatures: Arc::default() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn find_by_secret(&self, secret: &str) -> Option<&ApiKey> {
+        // Compare against every key so timing does not reveal which one matched
+        let mut found = None;
+        for key in self.keys.values() {
+            if bool::from(key.secret.as_bytes().ct_eq(secret.as_bytes())) {
+                found = Some(key);
+            }
+        }
+        found
+    }
+}
+
+/// Authenticates admin requests with either `Authorization: Bearer <secret>` or an
+/// HMAC-SHA256 signature, and checks the key has the scope the HTTP method needs.
+///
+/// Signed requests send `X-Api-Key-Id`, `X-Signature-Timestamp` (unix seconds) and
+/// `X-Signature`, the hex HMAC of `timestamp\nMETHOD\npath?query\nbody`. Each
+/// signature is accepted once.
+pub async fn require_api_key(
+    State(keys): State<ApiKeyStore>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let required = Scope::required_for(request.method());
+
+    let (key, mut request) = if let Some(secret) = bearer_token(request.headers()) {
+        let key = keys.find_by_secret(&secret).cloned().ok_or_else(|| {
+            warn!("Rejected admin request with unknown bearer key");
+            StatusCode::UNAUTHORIZED
+        })?;
+        (key, request)
+    } else if request.headers().contains_key("x-signature") {
+        verify_signature(&keys, request).await?
+    } else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    if !key.scopes.contains(&required) {
+        warn!("API key {} lacks {:?} scope for {} {}", key.id, required, request.method(), request.uri().path());
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    debug!("Authenticated admin request with API key {}", key.id);
+    request.extensions_mut().insert(AuthenticatedKey { id: key.id, scopes: key.scopes });
+    Ok(next.run(request).await)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, StatusCode> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+async fn verify_signature(keys: &ApiKeyStore, request: Request) -> Result<(ApiKey, Request), StatusCode> {
+    let headers = request.headers();
+    let key_id = header_str(headers, "x-api-key-id")?;
+    let timestamp = header_str(headers, "x-signature-timestamp")?.to_string();
+    let signature = hex::decode(header_str(headers, "x-signature")?).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let key = keys.keys.get(key_id).cloned().ok_or_else(|| {
+        warn!("Rejected signed request for unknown API key {}", key_id);
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    let signed_at = timestamp.parse::<i64>().map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let now = Utc::now().timestamp();
+    if (now - signed_at).abs() > MAX_SIGNATURE_AGE_SECS {
+        warn!("Rejected signed request for API key {} with stale timestamp", key.id);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_SIGNED_BODY_BYTES)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    // Sign the path the client sent, not the one left after router nesting
+    let uri = parts.extensions.get::<OriginalUri>().map(|original| &original.0).unwrap_or(&parts.uri);
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.secret.as_bytes())
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    mac.update(format!("{}\n{}\n{}\n", timestamp, parts.method, path).as_bytes());
+    mac.update(&body);
+
+    if mac.verify_slice(&signature).is_err() {
+        warn!("Rejected signed request with invalid signature for API key {}", key.id);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    // Only valid signatures are remembered, so forged requests cannot fill the cache
+    if !keys.seen_signatures.lock().unwrap().insert(signature, signed_at, now) {
+        warn!("Rejected replayed signed request for API key {}", key.id);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok((key, Request::from_parts(parts, Body::from(body))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let keys = ApiKeyStore::parse(["ops:ops-secret:read,write", "viewer:viewer-secret:read"]).unwrap();
+        Router::new()
+            .route("/items", get(|| async { "ok" }).post(|body: String| async move { body }))
+            .route_layer(middleware::from_fn_with_state(keys, require_api_key))
+    }
+
+    fn bearer(method: Method, secret: &str) -> Request {
+        Request::builder()
+            .method(method)
+            .uri("/items")
+            .header(header::AUTHORIZATION, format!("Bearer {}", secret))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn sign(secret: &str, timestamp: i64, method: &str, path: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}\n{}\n{}\n{}", timestamp, method, path, body).as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn signed(key_id: &str, timestamp: i64, signature: &str, body: &str) -> Request {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/items?dry_run=true")
+            .header("x-api-key-id", key_id)
+            .header("x-signature-timestamp", timestamp.to_string())
+            .header("x-signature", signature)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn status(request: Request) -> StatusCode {
+        app().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn bearer_keys_need_the_scope_of_the_method() {
+        assert_eq!(status(bearer(Method::GET, "ops-secret")).await, StatusCode::OK);
+        assert_eq!(status(bearer(Method::POST, "ops-secret")).await, StatusCode::OK);
+        assert_eq!(status(bearer(Method::GET, "viewer-secret")).await, StatusCode::OK);
+        assert_eq!(status(bearer(Method::POST, "viewer-secret")).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn unknown_or_missing_credentials_are_rejected() {
+        assert_eq!(status(bearer(Method::GET, "ops")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(bearer(Method::GET, "ops-secret-2")).await, StatusCode::UNAUTHORIZED);
+        let anonymous = Request::builder().uri("/items").body(Body::empty()).unwrap();
+        assert_eq!(status(anonymous).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn signed_requests_are_verified_over_the_body() {
+        let now = Utc::now().timestamp();
+        let signature = sign("ops-secret", now, "POST", "/items?dry_run=true", "{}");
+
+        let response = app().oneshot(signed("ops", now, &signature, "{}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // The handler still receives the body that was read for verification
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "{}");
+
+        let signature = sign("ops-secret", now, "POST", "/items?dry_run=true", "{}");
+        assert_eq!(status(signed("ops", now, &signature, "[]")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(signed("viewer", now, &signature, "{}")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(signed("nobody", now, &signature, "{}")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(signed("ops", now, "not hex", "{}")).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn signed_timestamps_must_be_within_the_window() {
+        let now = Utc::now().timestamp();
+        for timestamp in [now - MAX_SIGNATURE_AGE_SECS - 5, now + MAX_SIGNATURE_AGE_SECS + 5] {
+            let signature = sign("ops-secret", timestamp, "POST", "/items?dry_run=true", "");
+            assert_eq!(status(signed("ops", timestamp, &signature, "")).await, StatusCode::UNAUTHORIZED);
+        }
+
+        let timestamp = now - MAX_SIGNATURE_AGE_SECS + 5;
+        let signature = sign("ops-secret", timestamp, "POST", "/items?dry_run=true", "");
+        assert_eq!(status(signed("ops", timestamp, &signature, "")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn signed_requests_cannot_be_replayed() {
+        let app = app();
+        let now = Utc::now().timestamp();
+        let signature = sign("ops-secret", now, "POST", "/items?dry_run=true", "{}");
+
+        let first = app.clone().oneshot(signed("ops", now, &signature, "{}")).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let replay = app.oneshot(signed("ops", now, &signature, "{}")).await.unwrap();
+        assert_eq!(replay.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn seen_signatures_are_forgotten_once_outside_the_window() {
+        let mut seen = SeenSignatures::default();
+        assert!(seen.insert(vec![1], 1_000, 1_000));
+        assert!(!seen.insert(vec![1], 1_000, 1_010));
+        assert!(seen.insert(vec![2], 1_300, 1_300));
+        assert!(seen.signed_at.contains_key(&vec![1]));
+
+        // Pruning runs at most once per window
+        seen.insert(vec![3], 1_400, 1_400);
+        assert!(seen.signed_at.contains_key(&vec![1]));
+        seen.insert(vec![4], 1_600, 1_600);
+        assert!(!seen.signed_at.contains_key(&vec![1]));
+        assert!(seen.signed_at.contains_key(&vec![2]));
+    }
+
+    #[test]
+    fn key_specs_are_validated() {
+        let keys = ApiKeyStore::parse(["# comment", "", "ops:s3cr3t:read,delete"]).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert!(keys.find_by_secret("s3cr3t").is_some_and(|key| key.scopes.contains(&Scope::Delete)));
+
+        for spec in ["ops:secret", ":secret:read", "ops::read", "ops:secret:admin"] {
+            assert!(ApiKeyStore::parse([spec]).is_err(), "{:?}", spec);
+        }
+        assert!(ApiKeyStore::parse(["ops:a:read", "ops:b:read"]).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use futures::stream::{self, Stream, StreamExt};
+use rust_xlsxwriter::Workbook;
+use std::io::Seek;
+use tokio_util::io::ReaderStream;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use tracing::{error, info};
+use crate::domain::{models::{EntryFilter, GameType, SpinResult}, repository::Error};
+use super::extract::Game;
+use super::handlers::{AppService, ResultsQuery, error_status};
+
+const COLUMNS: [&str; 9] = [
+    "page_id", "game", "key", "datetime", "number", "is_win", "checked", "fulfilled_by", "fulfilled_at",
+];
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+    Xlsx,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Jsonl => "application/x-ndjson",
+            Self::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+            Self::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// A flattened result as it appears in every export format.
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    page_id: Option<&'a str>,
+    game: &'static str,
+    key: &'a str,
+    datetime: &'a str,
+    number: i32,
+    is_win: bool,
+    checked: bool,
+    fulfilled_by: Option<&'a str>,
+    fulfilled_at: Option<&'a str>,
+}
+
+impl<'a> ExportRow<'a> {
+    fn new(spin_result: &'a SpinResult, game_type: GameType) -> Self {
+        Self {
+            page_id: spin_result.page_id.as_deref(),
+            game: game_type.as_str(),
+            key: &spin_result.key,
+            datetime: &spin_result.datetime,
+            number: spin_result.number,
+            is_win: spin_result.is_win,
+            checked: spin_result.checked,
+            fulfilled_by: spin_result.fulfilled_by.as_deref(),
+            fulfilled_at: spin_result.fulfilled_at.as_deref(),
+        }
+    }
+}
+
+/// Streams every matching result, one Notion page at a time.
+#[utoipa::path(
+    get,
+    path = "/games/{game}/results/export",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ExportQuery, ResultsQuery),
+    responses((
+        status = 200,
+        description = "Matching results as a file download",
+        content(
+            ("text/csv" = String),
+            ("application/x-ndjson" = String),
+            ("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" = Vec<u8>),
+        ),
+    )),
+    security(("api_key" = [])),
+)]
+pub async fn export_spin_results(
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    Query(export): Query<ExportQuery>,
+    Query(query): Query<ResultsQuery>,
+) -> Result<Response, StatusCode> {
+    let filter: EntryFilter = query.into();
+    let format = export.format;
+    info!("Exporting results for game type {:?} as {:?} with filter: {:?}", game_type, format, filter);
+
+    let body = match format {
+        ExportFormat::Csv => Body::from_stream(text_stream(service, game_type, filter, Some(csv_header()), csv_rows)),
+        ExportFormat::Jsonl => Body::from_stream(text_stream(service, game_type, filter, None, jsonl_rows)),
+        ExportFormat::Xlsx => xlsx_workbook(&service, game_type, &filter).await?,
+    };
+
+    let disposition = format!("attachment; filename=\"{}-results.{}\"", game_type.as_str(), format.extension());
+    Ok((
+        [(header::CONTENT_TYPE, format.content_type().to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        body,
+    )
+        .into_response())
+}
+
+/// Pages of results following Notion's cursors until the last one.
+fn result_pages(
+    service: AppService,
+    game_type: GameType,
+    filter: EntryFilter,
+) -> impl Stream<Item = Result<Vec<SpinResult>, Error>> {
+    // `None` once the final page has been yielded or a request has failed
+    let start: Option<Option<String>> = Some(None);
+    stream::unfold((service, filter, start), move |(service, filter, cursor)| async move {
+        let cursor = cursor?;
+        match service.page_spin_results(game_type, &filter, cursor.as_deref()).await {
+            Ok(page) => {
+                let next = page.next_cursor.map(Some);
+                Some((Ok(page.entries), (service, filter, next)))
+            }
+            Err(err) => Some((Err(err), (service, filter, None))),
+        }
+    })
+}
+
+fn text_stream(
+    service: AppService,
+    game_type: GameType,
+    filter: EntryFilter,
+    header: Option<Bytes>,
+    encode: fn(&[SpinResult], GameType) -> Result<Bytes, BoxError>,
+) -> impl Stream<Item = Result<Bytes, BoxError>> {
+    let rows = result_pages(service, game_type, filter).map(move |page| {
+        let spin_results = page.map_err(|err| {
+            error!("Export failed part way for game type {:?}: {}", game_type, err);
+            BoxError::from(err)
+        })?;
+        encode(&spin_results, game_type)
+    });
+
+    stream::iter(header.map(Ok)).chain(rows)
+}
+
+fn csv_header() -> Bytes {
+    let mut header = COLUMNS.join(",");
+    header.push('\n');
+    Bytes::from(header)
+}
+
+fn csv_rows(spin_results: &[SpinResult], game_type: GameType) -> Result<Bytes, BoxError> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    for spin_result in spin_results {
+        writer.serialize(ExportRow::new(spin_result, game_type))?;
+    }
+    Ok(Bytes::from(writer.into_inner().map_err(|err| err.into_error())?))
+}
+
+fn jsonl_rows(spin_results: &[SpinResult], game_type: GameType) -> Result<Bytes, BoxError> {
+    let mut buffer = Vec::new();
+    for spin_result in spin_results {
+        serde_json::to_writer(&mut buffer, &ExportRow::new(spin_result, game_type))?;
+        buffer.push(b'\n');
+    }
+    Ok(Bytes::from(buffer))
+}
+
+/// XLSX is a zip archive and can only be sent once complete, so rows are
+/// spooled to disk by the constant-memory worksheet while pages are fetched, and
+/// the finished workbook is written to a temporary file that is then streamed.
+async fn xlsx_workbook(service: &AppService, game_type: GameType, filter: &EntryFilter) -> Result<Body, StatusCode> {
+    let failed = |err: rust_xlsxwriter::XlsxError| {
+        error!("Failed to build XLSX export for game type {:?}: {}", game_type, err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+    for (col, name) in COLUMNS.iter().enumerate() {
+        worksheet.write_string(0, col as u16, *name).map_err(failed)?;
+    }
+
+    let mut row = 1;
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = service
+            .page_spin_results(game_type, filter, cursor.as_deref())
+            .await
+            .map_err(error_status)?;
+
+        for spin_result in &page.entries {
+            let fields = ExportRow::new(spin_result, game_type);
+            worksheet.write_string(row, 0, fields.page_id.unwrap_or_default()).map_err(failed)?;
+            worksheet.write_string(row, 1, fields.game).map_err(failed)?;
+            worksheet.write_string(row, 2, fields.key).map_err(failed)?;
+            worksheet.write_string(row, 3, fields.datetime).map_err(failed)?;
+            worksheet.write_number(row, 4, fields.number).map_err(failed)?;
+            worksheet.write_boolean(row, 5, fields.is_win).map_err(failed)?;
+            worksheet.write_boolean(row, 6, fields.checked).map_err(failed)?;
+            worksheet.write_string(row, 7, fields.fulfilled_by.unwrap_or_default()).map_err(failed)?;
+            worksheet.write_string(row, 8, fields.fulfilled_at.unwrap_or_default()).map_err(failed)?;
+            row += 1;
+        }
+
+        cursor = match page.next_cursor {
+            Some(next) => Some(next),
+            None => break,
+        };
+    }
+
+    // The anonymous temporary file is removed once the response has been sent
+    let file = tokio::task::spawn_blocking(move || -> Result<std::fs::File, rust_xlsxwriter::XlsxError> {
+        let mut file = tempfile::tempfile()?;
+        workbook.save_to_writer(&mut file)?;
+        file.rewind()?;
+        Ok(file)
+    })
+    .await
+    .map_err(|err| {
+        error!("XLSX export task failed for game type {:?}: {}", game_type, err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .map_err(failed)?;
+
+    Ok(Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(file))))
+}
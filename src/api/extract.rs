@@ -0,0 +1,136 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Path, Request},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::debug;
+use validator::Validate;
+use crate::domain::models::GameType;
+
+async fn path_param<S: Send + Sync>(parts: &mut Parts, state: &S, name: &str) -> Option<String> {
+    let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+        .await
+        .ok()?;
+    params.get(name).cloned()
+}
+
+/// The game a result route operates on.
+///
+/// Taken from a `GameType` extension when the router pins the game (the legacy
+/// `/spin-results` routes), otherwise from the `:game` path parameter. Unknown
+/// games are rejected with 404.
+pub struct Game(pub GameType);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Game {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(game_type) = parts.extensions.get::<GameType>() {
+            return Ok(Self(*game_type));
+        }
+
+        let game = path_param(parts, state, "game").await.ok_or(StatusCode::NOT_FOUND)?;
+        game.parse::<GameType>().map(Self).map_err(|err| {
+            debug!("{}", err);
+            StatusCode::NOT_FOUND
+        })
+    }
+}
+
+/// The `:page_id` path parameter, which sits next to `:game` on game-scoped routes.
+pub struct PageId(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PageId {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        path_param(parts, state, "page_id")
+            .await
+            .map(Self)
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+}
+
+/// A JSON body that also passes its `Validate` rules. Bodies breaking a rule are
+/// rejected with 422 and `{"errors": {field: [...]}}` before any handler runs.
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        value.validate().map_err(|errors| {
+            debug!("Rejected invalid body: {}", errors);
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "errors": errors }))).into_response()
+        })?;
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::SpinResult;
+    use axum::{body::{to_bytes, Body}, http::header, routing::post, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn post_result(body: &str) -> (StatusCode, Value) {
+        let app = Router::new().route("/results", post(|ValidatedJson(spin_result): ValidatedJson<SpinResult>| async move {
+            spin_result.key
+        }));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/results")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn invalid_bodies_list_every_broken_field() {
+        let (status, body) = post_result(r#"{"key": "bad key", "datetime": "2024-03-15", "number": 1000, "is_win": false, "checked": false}"#).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let errors = body["errors"].as_object().expect("errors object");
+        let mut fields: Vec<_> = errors.keys().map(String::as_str).collect();
+        fields.sort();
+        assert_eq!(fields, ["datetime", "key", "number"]);
+        assert_eq!(errors["key"][0]["code"], "charset");
+        assert_eq!(errors["key"][0]["message"], "may only contain letters, digits and - _ . @ + :");
+        assert_eq!(errors["datetime"][0]["code"], "rfc3339");
+        assert_eq!(errors["number"][0]["message"], "must be between 0 and 999");
+    }
+
+    #[tokio::test]
+    async fn valid_bodies_reach_the_handler() {
+        let (status, _) = post_result(r#"{"key": "p1", "datetime": "2024-03-15T12:00:00Z", "number": 7, "is_win": true, "checked": false}"#).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_rejected_before_validation() {
+        let (status, body) = post_result(r#"{"key": "p1""#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, Value::Null);
+    }
+}
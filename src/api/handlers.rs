@@ -1,67 +1,522 @@
 use axum::{
-    extract::{State, Path},
-    response::Json,
-    http::StatusCode,
+    extract::{State, Path, Query},
+    Extension,
+    response::{IntoResponse, Json, Response},
+    http::{header, StatusCode},
 };
 use crate::{
-    domain::models::{SpinResult, SpinRequest, SpinResponse, WheelRequest, WheelResponse, GameType},
+    domain::models::{
+        SpinResult, SpinResultPatch, ArchivedResult, SpinRequest, SpinResponse, WheelRequest, WheelResponse, GameType,
+        BulkOperation, BulkRequest, BulkResponse, PlayerStatus, PlayerHistoryEntry, EntryFilter,
+        Stats, StatsBucket, Campaign, Readiness,
+    },
     domain::repository::Error,
-    application::services::NotionService,
-    infrastructure::notion::NotionClient,
+    application::{services::NotionService, stats::StatsQuery},
+    infrastructure::{notion::NotionClient, mirror::MirroredRepository},
+    metrics, reload,
 };
+use super::identity::PlayerIdentity;
+use super::extract::{Game, PageId, ValidatedJson};
+use super::auth::{AuthenticatedKey, Scope};
 use rand::{rngs::SmallRng, SeedableRng, Rng};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use utoipa::IntoParams;
 
+pub type AppService = NotionService<MirroredRepository<NotionClient>>;
+
+const MAX_BULK_OPERATIONS: usize = 1000;
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+const MAX_HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Only this game, `spin` or `wheel`
+    pub game: Option<String>,
+    /// Most recent entries to return, at most 100
+    pub limit: Option<usize>,
+}
+
+/// Filters accepted by the result listing and export endpoints.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResultsQuery {
+    pub key: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub is_win: Option<bool>,
+    pub checked: Option<bool>,
+}
+
+impl From<ResultsQuery> for EntryFilter {
+    fn from(query: ResultsQuery) -> Self {
+        Self {
+            key: query.key,
+            from: query.from,
+            to: query.to,
+            is_win: query.is_win,
+            checked: query.checked,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsParams {
+    /// Only this game, `spin` or `wheel`; every game when omitted
+    pub game: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub bucket: StatsBucket,
+}
+
+pub(super) fn error_status(err: Error) -> StatusCode {
+    match err {
+        Error::SpinLimitReached => StatusCode::TOO_MANY_REQUESTS,
+        Error::NotFound => StatusCode::NOT_FOUND,
+        Error::InvalidPageId(_) => StatusCode::BAD_REQUEST,
+        Error::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        Error::AlreadyFulfilled => StatusCode::CONFLICT,
+        Error::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/games/{game}/results",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage")),
+    request_body = SpinResult,
+    responses(
+        (status = 201, description = "Result created"),
+        (status = 422, description = "The result breaks a validation rule, with `errors` by field"),
+        (status = 429, description = "The player reached their play limit"),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn create_spin_result(
-    State(service): State<NotionService<NotionClient>>,
-    Json(spin_result): Json<SpinResult>,
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    ValidatedJson(spin_result): ValidatedJson<SpinResult>,
 ) -> Result<StatusCode, StatusCode> {
-    match service.create_spin_result(spin_result, GameType::Spin).await {
+    match service.create_spin_result(spin_result, game_type).await {
         Ok(_) => Ok(StatusCode::CREATED),
-        Err(Error::SpinLimitReached) => Err(StatusCode::TOO_MANY_REQUESTS),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(err) => Err(error_status(err)),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/games/{game}/results",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ResultsQuery),
+    responses((status = 200, description = "Matching results", body = Vec<SpinResult>)),
+    security(("api_key" = [])),
+)]
 pub async fn get_spin_results(
-    State(service): State<NotionService<NotionClient>>,
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    Query(query): Query<ResultsQuery>,
 ) -> Result<Json<Vec<SpinResult>>, StatusCode> {
     service
-        .get_spin_results(GameType::Spin)
+        .get_spin_results(game_type, &query.into())
         .await
         .map(Json)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
+#[utoipa::path(
+    put,
+    path = "/games/{game}/results/{page_id}",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ("page_id" = String, Path, description = "Notion page id, with or without dashes")),
+    request_body = SpinResult,
+    responses(
+        (status = 200, description = "Result updated"),
+        (status = 400, description = "Invalid page id"),
+        (status = 404, description = "Result not found"),
+        (status = 422, description = "The result breaks a validation rule, with `errors` by field"),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn update_spin_result(
-    State(service): State<NotionService<NotionClient>>,
-    Path(page_id): Path<String>,
-    Json(spin_result): Json<SpinResult>,
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    PageId(page_id): PageId,
+    ValidatedJson(spin_result): ValidatedJson<SpinResult>,
 ) -> StatusCode {
-    match service.update_spin_result(&page_id, spin_result, GameType::Spin).await {
+    match service.update_spin_result(&page_id, spin_result, game_type).await {
         Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        Err(err) => error_status(err),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/games/{game}/results/{page_id}",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ("page_id" = String, Path, description = "Notion page id, with or without dashes")),
+    request_body = SpinResultPatch,
+    responses(
+        (status = 200, description = "The updated result", body = SpinResult),
+        (status = 400, description = "Invalid page id"),
+        (status = 404, description = "Result not found"),
+        (status = 422, description = "The patch breaks a validation rule, with `errors` by field"),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn patch_spin_result(
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    PageId(page_id): PageId,
+    ValidatedJson(patch): ValidatedJson<SpinResultPatch>,
+) -> Result<Json<SpinResult>, StatusCode> {
+    service
+        .patch_spin_result(&page_id, patch, game_type)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+#[utoipa::path(
+    post,
+    path = "/games/{game}/results/{page_id}/fulfil",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ("page_id" = String, Path, description = "Notion page id, with or without dashes")),
+    responses(
+        (status = 200, description = "The fulfilled result", body = SpinResult),
+        (status = 404, description = "Result not found"),
+        (status = 409, description = "Prize already fulfilled"),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn fulfil_spin_result(
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    Extension(api_key): Extension<AuthenticatedKey>,
+    PageId(page_id): PageId,
+) -> Result<Json<SpinResult>, StatusCode> {
+    service
+        .fulfil_spin_result(&page_id, &api_key.id, game_type)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/games/{game}/results/{page_id}",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ("page_id" = String, Path, description = "Notion page id, with or without dashes")),
+    responses(
+        (status = 204, description = "Result archived"),
+        (status = 404, description = "Result not found"),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn delete_spin_result(
-    State(service): State<NotionService<NotionClient>>,
-    Path(page_id): Path<String>,
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    PageId(page_id): PageId,
 ) -> StatusCode {
-    match service.delete_spin_result(&page_id, GameType::Spin).await {
+    match service.delete_spin_result(&page_id, game_type).await {
         Ok(_) => StatusCode::NO_CONTENT,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        Err(err) => error_status(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/games/{game}/results/{page_id}/restore",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage"), ("page_id" = String, Path, description = "Notion page id, with or without dashes")),
+    responses(
+        (status = 200, description = "The restored result", body = SpinResult),
+        (status = 404, description = "Result not found"),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn restore_spin_result(
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    PageId(page_id): PageId,
+) -> Result<Json<SpinResult>, StatusCode> {
+    service
+        .restore_spin_result(&page_id, game_type)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+#[utoipa::path(
+    get,
+    path = "/games/{game}/results/trash",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage")),
+    responses((status = 200, description = "Results archived in the last 30 days", body = Vec<ArchivedResult>)),
+    security(("api_key" = [])),
+)]
+pub async fn get_archived_spin_results(
+    State(service): State<AppService>,
+    Game(game_type): Game,
+) -> Result<Json<Vec<ArchivedResult>>, StatusCode> {
+    service
+        .get_archived_spin_results(game_type)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+#[utoipa::path(
+    post,
+    path = "/games/{game}/results/bulk",
+    tag = "results",
+    params(("game" = GameType, Path, description = "Game whose results to manage")),
+    request_body = BulkRequest,
+    responses(
+        (status = 200, description = "Outcome of each operation", body = BulkResponse),
+        (status = 403, description = "Archiving needs the delete scope"),
+        (status = 413, description = "More than 1000 operations"),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn bulk_spin_results(
+    State(service): State<AppService>,
+    Game(game_type): Game,
+    Extension(api_key): Extension<AuthenticatedKey>,
+    Json(request): Json<BulkRequest>,
+) -> Result<Json<BulkResponse>, StatusCode> {
+    if request.operations.len() > MAX_BULK_OPERATIONS {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    // Archiving through a bulk request still needs the delete scope
+    let archives = request.operations.iter().any(|operation| matches!(operation, BulkOperation::Archive { .. }));
+    if archives && !api_key.has_scope(Scope::Delete) {
+        return Err(StatusCode::FORBIDDEN);
     }
+
+    Ok(Json(service.run_bulk(request.operations, game_type).await))
 }
 
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "admin",
+    params(StatsParams),
+    responses(
+        (status = 200, description = "Totals and per-period counts", body = Stats),
+        (status = 400, description = "Unknown game"),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_stats(
+    State(service): State<AppService>,
+    Query(params): Query<StatsParams>,
+) -> Result<Json<Stats>, StatusCode> {
+    let game = params
+        .game
+        .map(|game| game.parse::<GameType>())
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let query = StatsQuery {
+        game,
+        from: params.from,
+        to: params.to,
+        bucket: params.bucket,
+    };
+
+    service
+        .stats(query)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+/// Reloads game settings from the configuration. 422 with the problems found if it is invalid.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 204, description = "Game settings reloaded"),
+        (status = 422, description = "The configuration is invalid, with `problems` listing why"),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn reload_config(State(service): State<AppService>) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    match reload::reload(&service, "POST /admin/reload") {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(report) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "problems": report.problems })))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/players/{key}/status",
+    tag = "play",
+    params(("key" = String, Path, description = "Player key")),
+    responses(
+        (status = 200, description = "Remaining plays per game", body = PlayerStatus),
+        (status = 401, description = "Missing or invalid player token"),
+        (status = 403, description = "The player token is for another player"),
+    ),
+    security(("player_token" = [])),
+)]
+pub async fn get_player_status(
+    State(service): State<AppService>,
+    identity: PlayerIdentity,
+    Path(key): Path<String>,
+) -> Result<Json<PlayerStatus>, StatusCode> {
+    if !identity.can_access(&key) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    service
+        .player_status(&key)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+#[utoipa::path(
+    get,
+    path = "/players/{key}/history",
+    tag = "play",
+    params(("key" = String, Path, description = "Player key"), HistoryQuery),
+    responses(
+        (status = 200, description = "Recent outcomes, newest first", body = Vec<PlayerHistoryEntry>),
+        (status = 400, description = "Unknown game"),
+        (status = 401, description = "Missing or invalid player token"),
+        (status = 403, description = "The player token is for another player"),
+    ),
+    security(("player_token" = [])),
+)]
+pub async fn get_player_history(
+    State(service): State<AppService>,
+    identity: PlayerIdentity,
+    Path(key): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<PlayerHistoryEntry>>, StatusCode> {
+    if !identity.can_access(&key) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let game_type = query
+        .game
+        .map(|game| game.parse::<GameType>())
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_HISTORY_LIMIT);
+
+    service
+        .player_history(&key, game_type, limit)
+        .await
+        .map(Json)
+        .map_err(error_status)
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "health",
+    responses((status = 200, description = "The API is running", body = String, content_type = "text/plain")),
+)]
 pub async fn get_root() -> &'static str {
     "Notion API is running"
 }
 
+/// Prometheus metrics in the text exposition format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "admin",
+    responses((status = 200, description = "Prometheus metrics", body = String, content_type = "text/plain")),
+    security(("api_key" = [])),
+)]
+pub async fn get_metrics() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], metrics::render())
+}
+
+/// Liveness: the process is up and serving requests.
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "The process is serving requests", body = String, content_type = "text/plain")),
+)]
+pub async fn get_healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness: Notion accepts the token and every configured database can be read.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is reachable", body = Readiness),
+        (status = 503, description = "At least one dependency failed its check", body = Readiness),
+    ),
+)]
+pub async fn get_readyz(State(service): State<AppService>) -> (StatusCode, Json<Readiness>) {
+    let readiness = service.readiness().await;
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness))
+}
+
+/// Campaigns running right now, so the frontend knows what to offer.
+#[utoipa::path(
+    get,
+    path = "/campaigns/active",
+    tag = "play",
+    responses((status = 200, description = "Campaigns running now", body = Vec<Campaign>)),
+)]
+pub async fn get_active_campaigns(State(service): State<AppService>) -> Json<Vec<Campaign>> {
+    Json(service.active_campaigns())
+}
+
+/// The campaign a play asked for. Plays naming an unknown campaign, or one
+/// outside its start/end window, are refused with a message saying why.
+fn play_campaign(service: &AppService, campaign: Option<String>, game_type: GameType) -> Result<Option<Campaign>, (StatusCode, Json<Value>)> {
+    let Some(id) = campaign else {
+        return Ok(None);
+    };
+
+    service.running_campaign(&id, game_type).map(Some).map_err(|err| {
+        let status = match err {
+            Error::UnknownCampaign(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::FORBIDDEN,
+        };
+        (status, Json(json!({ "error": err.to_string() })))
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/spin-result",
+    tag = "play",
+    request_body = SpinRequest,
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for retries with the same key")),
+    responses(
+        (status = 200, description = "The numbers drawn", body = SpinResponse),
+        (status = 403, description = "The campaign is not running"),
+        (status = 404, description = "Unknown campaign"),
+        (status = 429, description = "The player reached their play limit"),
+    ),
+    security((), ("player_token" = [])),
+)]
 pub async fn spin_result(
-    State(service): State<NotionService<NotionClient>>,
+    State(service): State<AppService>,
+    identity: PlayerIdentity,
     Json(request): Json<SpinRequest>,
-) -> Result<Json<SpinResponse>, StatusCode> {
+) -> Result<Json<SpinResponse>, Response> {
+    let campaign = play_campaign(&service, request.campaign, GameType::Spin).map_err(IntoResponse::into_response)?;
+
     // Generate three random numbers using a thread-safe RNG
     let mut rng = SmallRng::from_entropy();
     let win_chance = 0.00; // 0% chance to win
@@ -92,10 +547,14 @@ pub async fn spin_result(
         
         (non_winning_numbers, false)
     };
+    // Losing numbers are not prizes, and labelling each one would make a series per combination
+    let prize = if is_win { numbers.join("") } else { "none".to_string() };
+    metrics::record_play(GameType::Spin, &prize, is_win);
+    service.count_play(GameType::Spin).await;
     
     // Save to Notion only if it's a win
     if is_win {
-        let key = request.key.unwrap_or_else(|| Utc::now().timestamp_millis().to_string());
+        let key = identity.key_or(request.key).unwrap_or_else(|| Utc::now().timestamp_millis().to_string());
         let now = Utc::now().to_rfc3339();
         
         // Join the numbers to a single integer
@@ -109,12 +568,17 @@ pub async fn spin_result(
             is_win,
             checked: false,
             game_type: Some("Spin".to_string()),
+            ..Default::default()
         };
         
-        if let Err(err) = service.create_spin_result(spin_result, GameType::Spin).await {
+        let created = match &campaign {
+            Some(campaign) => service.create_campaign_result(spin_result, campaign).await,
+            None => service.create_spin_result(spin_result, GameType::Spin).await,
+        };
+        if let Err(err) = created {
             match err {
-                Error::SpinLimitReached => return Err(StatusCode::TOO_MANY_REQUESTS),
-                _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Error::SpinLimitReached => return Err(StatusCode::TOO_MANY_REQUESTS.into_response()),
+                _ => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
             }
         }
     }
@@ -128,44 +592,41 @@ pub async fn spin_result(
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/wheel-result",
+    tag = "play",
+    request_body = WheelRequest,
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for retries with the same key")),
+    responses(
+        (status = 200, description = "The slice the wheel stopped on", body = WheelResponse),
+        (status = 403, description = "The campaign is not running"),
+        (status = 404, description = "Unknown campaign"),
+    ),
+    security((), ("player_token" = [])),
+)]
 pub async fn wheel_result(
-    State(service): State<NotionService<NotionClient>>,
+    State(service): State<AppService>,
+    identity: PlayerIdentity,
     Json(request): Json<WheelRequest>,
-) -> Result<Json<WheelResponse>, StatusCode> {
-    // Initialize the RNG and handle any potential errors
-    let mut rng = match SmallRng::from_entropy() {
-        rng => rng,
-    };
-    
-    // Define the prize weights for the wheel (copied from frontend)
-    let weights = [
-        0,   // รับเครดิต 500 - not possible to land
-        30,  // หมุนฟรี 1 ครั้ง - medium chance
-        5,   // รับเครดิต 50 - low chance
-        35,  // แย่จัง - high chance
-        0,   // รับเครดิต 300 - not possible to land
-        30,  // หมุนฟรี 1 ครั้ง - medium chance
-        5,   // รับเครดิต 100 - low chance
-        35   // แย่จัง - high chance
-    ];
-
-    // Define the prize names (copied from frontend)
-    let slice_prizes = [
-        "รับเครดิต 500", 
-        "หมุนฟรี 1 ครั้ง", 
-        "รับเครดิต 50", 
-        "แย่จัง", 
-        "รับเครดิต 300", 
-        "หมุนฟรี 1 ครั้ง", 
-        "รับเครดิต 100", 
-        "แย่จัง"
-    ];
+) -> Result<Json<WheelResponse>, Response> {
+    let campaign = play_campaign(&service, request.campaign, GameType::Wheel).map_err(IntoResponse::into_response)?;
+
+    // Initialize the RNG
+    let mut rng = SmallRng::from_entropy();
+
+    // The prize table in force when the spin started, even if it is reloaded meanwhile
+    let settings = service.settings();
+    let prizes = campaign
+        .as_ref()
+        .and_then(|campaign| campaign.prizes.as_ref())
+        .unwrap_or(&settings.wheel_prizes);
     
     // Calculate the total weight
-    let total_weight: u32 = weights.iter().sum();
+    let total_weight: u32 = prizes.iter().map(|prize| prize.weight).sum();
     if total_weight == 0 {
         // Prevent division by zero or other issues with zero weight
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
     }
     
     // Generate a random number between 0 and the total weight
@@ -175,8 +636,8 @@ pub async fn wheel_result(
     let mut cumulative_weight = 0;
     let mut prize_index = 0;
     
-    for (i, &weight) in weights.iter().enumerate() {
-        cumulative_weight += weight;
+    for (i, prize) in prizes.iter().enumerate() {
+        cumulative_weight += prize.weight;
         if random_weight < cumulative_weight {
             prize_index = i;
             break;
@@ -184,16 +645,18 @@ pub async fn wheel_result(
     }
     
     // Safety check to ensure prize_index is valid
-    if prize_index >= slice_prizes.len() {
-        prize_index = slice_prizes.len() - 1;
+    if prize_index >= prizes.len() {
+        prize_index = prizes.len() - 1;
     }
     
     // Get the prize name
-    let prize_name = slice_prizes[prize_index].to_string();
+    let prize = &prizes[prize_index];
+    let prize_name = prize.label.clone();
     
-    // Determine if it's a win by checking specific prize indices
-    // Only prize_index 2 (รับเครดิต 50) and prize_index 6 (รับเครดิต 100) are winning results
-    let is_win = prize_index == 2 || prize_index == 6;
+    // Only slices marked as wins (the credit prizes by default) are winning results
+    let is_win = prize.is_win;
+    metrics::record_play(GameType::Wheel, &prize_name, is_win);
+    service.count_play(GameType::Wheel).await;
     
     // Create the response first, so we can return it even if saving to Notion fails
     let response = WheelResponse {
@@ -204,10 +667,8 @@ pub async fn wheel_result(
     
     // Try to save to Notion if it's a win, but don't fail the whole request if this fails
     if is_win {
-        let key = request.key.unwrap_or_else(|| Utc::now().timestamp_millis().to_string());
-        let now = match Utc::now().to_rfc3339() {
-            datetime => datetime,
-        };
+        let key = identity.key_or(request.key).unwrap_or_else(|| Utc::now().timestamp_millis().to_string());
+        let now = Utc::now().to_rfc3339();
         
         // For storing in database, we'll convert the prize_index to a number
         let number = prize_index as i32;
@@ -219,11 +680,15 @@ pub async fn wheel_result(
             is_win,
             checked: false,
             game_type: Some("Wheel".to_string()),
+            ..Default::default()
         };
         
         // Fire-and-forget approach: try to save but return the response regardless
         // This prevents the 500 error from propagating to the client
-        let _ = service.create_spin_result(spin_result, GameType::Wheel).await;
+        let _ = match &campaign {
+            Some(campaign) => service.create_campaign_result(spin_result, campaign).await,
+            None => service.create_spin_result(spin_result, GameType::Wheel).await,
+        };
     }
     
     // Always return the response, even if saving to Notion failed
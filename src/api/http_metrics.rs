@@ -0,0 +1,23 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use crate::metrics;
+
+/// Times every request and records it by route template, so `/players/:key/status`
+/// is one series however many players call it.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics::observe_http_request(&method, &path, response.status().as_u16(), started.elapsed());
+    response
+}
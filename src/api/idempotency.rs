@@ -0,0 +1,416 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, Extensions, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn, Instrument};
+use super::auth::AuthenticatedKey;
+use super::rate_limit::ClientIp;
+
+const MAX_IDEMPOTENT_BODY_BYTES: usize = 1024 * 1024;
+const MAX_KEY_LENGTH: usize = 255;
+
+enum Entry {
+    InFlight {
+        fingerprint: Vec<u8>,
+        started_at: Instant,
+    },
+    Done {
+        fingerprint: Vec<u8>,
+        stored_at: Instant,
+        status: StatusCode,
+        content_type: Option<HeaderValue>,
+        body: Bytes,
+    },
+}
+
+impl Entry {
+    fn created_at(&self) -> Instant {
+        match self {
+            Entry::InFlight { started_at, .. } => *started_at,
+            Entry::Done { stored_at, .. } => *stored_at,
+        }
+    }
+}
+
+/// Responses to `POST` requests that carried an `Idempotency-Key` header, kept for
+/// `ttl` and at most `max_entries` at a time, dropping the oldest first.
+#[derive(Clone)]
+pub struct IdempotencyStore {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<Vec<u8>, Entry>,
+    // Keys in the order their entries were written. A key rewritten later is
+    // queued again, and the stale position skipped when it comes up.
+    order: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl Entries {
+    fn insert(&mut self, key: Vec<u8>, entry: Entry) {
+        self.order.push_back((entry.created_at(), key.clone()));
+        self.by_key.insert(key, entry);
+    }
+
+    /// Drops the oldest entry, returning false once there is nothing left to drop.
+    fn pop_oldest(&mut self) -> bool {
+        let Some((created_at, key)) = self.order.pop_front() else {
+            return false;
+        };
+        if self.by_key.get(&key).is_some_and(|entry| entry.created_at() == created_at) {
+            self.by_key.remove(&key);
+        }
+        true
+    }
+
+    /// Drops expired entries, then the oldest ones until there is room for one more.
+    fn make_room(&mut self, now: Instant, ttl: Duration, max_entries: usize) {
+        while self.order.front().is_some_and(|(created_at, _)| now.duration_since(*created_at) >= ttl) {
+            self.pop_oldest();
+        }
+        while self.by_key.len() >= max_entries && self.pop_oldest() {}
+    }
+}
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Arc::default(),
+        }
+    }
+
+    fn remove(&self, key: &[u8]) {
+        self.entries.lock().unwrap().by_key.remove(key);
+    }
+}
+
+/// Clears an `InFlight` entry if the handler never got to store its response, for
+/// example because it panicked, so retries are not refused with 409 until the TTL.
+struct InFlightGuard {
+    store: IdempotencyStore,
+    key: Option<Vec<u8>>,
+}
+
+impl InFlightGuard {
+    fn disarm(mut self) -> Vec<u8> {
+        self.key.take().unwrap_or_default()
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.store.remove(&key);
+        }
+    }
+}
+
+fn digest(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Keys are scoped to the route and caller, so two players (or two admin keys)
+/// sending the same `Idempotency-Key` never see each other's responses. The caller
+/// is the authenticated admin key, else the `Authorization` header, else the client IP.
+fn scoped_key(method: &Method, path: &str, extensions: &Extensions, headers: &HeaderMap, idempotency_key: &str) -> Vec<u8> {
+    let caller = if let Some(api_key) = extensions.get::<AuthenticatedKey>() {
+        format!("api-key:{}", api_key.id).into_bytes()
+    } else if let Some(authorization) = headers.get(header::AUTHORIZATION) {
+        [b"authorization:".as_slice(), authorization.as_bytes()].concat()
+    } else if let Some(ClientIp(ip)) = extensions.get::<ClientIp>() {
+        format!("ip:{}", ip).into_bytes()
+    } else {
+        Vec::new()
+    };
+    digest(&[method.as_str().as_bytes(), path.as_bytes(), &caller, idempotency_key.as_bytes()])
+}
+
+/// Replays the stored response when a `POST` is retried with the same
+/// `Idempotency-Key`, instead of running the handler (and drawing) again.
+///
+/// A retry that arrives while the first request is still running gets 409, and
+/// reusing a key with a different body gets 422. Server errors are not stored so
+/// the client can retry them. The handler runs in its own task, so a client that
+/// disconnects or times out can still collect the outcome by retrying.
+pub async fn replay_idempotent(
+    State(store): State<IdempotencyStore>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(idempotency_key) = request
+        .headers()
+        .get("idempotency-key")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+    else {
+        return next.run(request).await;
+    };
+    if idempotency_key.is_empty() || idempotency_key.len() > MAX_KEY_LENGTH {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let key = scoped_key(request.method(), request.uri().path(), request.extensions(), request.headers(), &idempotency_key);
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_IDEMPOTENT_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let fingerprint = digest(&[&body]);
+
+    {
+        let mut entries = store.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.make_room(now, store.ttl, store.max_entries);
+
+        match entries.by_key.get(&key) {
+            Some(Entry::Done { fingerprint: stored, .. }) | Some(Entry::InFlight { fingerprint: stored, .. })
+                if *stored != fingerprint =>
+            {
+                warn!("Idempotency key reused with a different request body");
+                return StatusCode::UNPROCESSABLE_ENTITY.into_response();
+            }
+            Some(Entry::InFlight { .. }) => {
+                return StatusCode::CONFLICT.into_response();
+            }
+            Some(Entry::Done { status, content_type, body, .. }) => {
+                debug!("Replaying stored response for idempotency key");
+                let mut response = (*status, body.clone()).into_response();
+                if let Some(content_type) = content_type {
+                    response.headers_mut().insert(header::CONTENT_TYPE, content_type.clone());
+                }
+                response.headers_mut().insert("idempotent-replayed", HeaderValue::from_static("true"));
+                return response;
+            }
+            None => {
+                entries.insert(key.clone(), Entry::InFlight { fingerprint: fingerprint.clone(), started_at: now });
+            }
+        }
+    }
+
+    let guard = InFlightGuard { store: store.clone(), key: Some(key) };
+    let request = Request::from_parts(parts, Body::from(body));
+    let handler = tokio::spawn(async move {
+        let response = next.run(request).await;
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.ok()?;
+
+        // Server errors are dropped with the guard so they can be retried
+        if !parts.status.is_server_error() {
+            let key = guard.disarm();
+            store.entries.lock().unwrap().insert(key, Entry::Done {
+                fingerprint,
+                stored_at: Instant::now(),
+                status: parts.status,
+                content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+                body: body.clone(),
+            });
+        }
+        Some((parts, body))
+    }.in_current_span());
+
+    match handler.await {
+        Ok(Some((parts, body))) => Response::from_parts(parts, Body::from(body)),
+        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use std::net::IpAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    /// Counts handler runs and echoes the run number. `/flaky` fails on the first
+    /// run, and `/slow` waits for `release`.
+    fn app(store: IdempotencyStore, runs: Arc<AtomicUsize>, release: Arc<Notify>) -> Router {
+        let plays = runs.clone();
+        let flaky = runs.clone();
+        Router::new()
+            .route("/plays", post(move |body: String| async move {
+                let run = plays.fetch_add(1, Ordering::SeqCst) + 1;
+                (StatusCode::CREATED, format!("run {}: {}", run, body))
+            }))
+            .route("/flaky", post(move || async move {
+                match flaky.fetch_add(1, Ordering::SeqCst) {
+                    0 => StatusCode::BAD_GATEWAY,
+                    _ => StatusCode::CREATED,
+                }
+            }))
+            .route("/slow", post(move || async move {
+                release.notified().await;
+                StatusCode::CREATED
+            }))
+            .route_layer(middleware::from_fn_with_state(store, replay_idempotent))
+    }
+
+    struct Harness {
+        app: Router,
+        runs: Arc<AtomicUsize>,
+        release: Arc<Notify>,
+    }
+
+    impl Harness {
+        fn new(max_entries: usize) -> Self {
+            let runs = Arc::new(AtomicUsize::new(0));
+            let release = Arc::new(Notify::new());
+            let store = IdempotencyStore::new(Duration::from_secs(60), max_entries);
+            Self { app: app(store, runs.clone(), release.clone()), runs, release }
+        }
+
+        async fn send(&self, request: Request) -> (StatusCode, bool, String) {
+            let response = self.app.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let replayed = response.headers().contains_key("idempotent-replayed");
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, replayed, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        fn runs(&self) -> usize {
+            self.runs.load(Ordering::SeqCst)
+        }
+    }
+
+    fn post_with_key(path: &str, key: &str) -> axum::http::request::Builder {
+        Request::builder().method(Method::POST).uri(path).header("idempotency-key", key)
+    }
+
+    fn request(path: &str, key: &str, body: &str) -> Request {
+        post_with_key(path, key).body(Body::from(body.to_string())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_replay_the_stored_response() {
+        let harness = Harness::new(100);
+
+        let first = harness.send(request("/plays", "k1", "a")).await;
+        assert_eq!(first, (StatusCode::CREATED, false, "run 1: a".to_string()));
+        let retry = harness.send(request("/plays", "k1", "a")).await;
+        assert_eq!(retry, (StatusCode::CREATED, true, "run 1: a".to_string()));
+        assert_eq!(harness.runs(), 1);
+
+        // Another key, or no key at all, runs the handler again
+        assert_eq!(harness.send(request("/plays", "k2", "a")).await.2, "run 2: a");
+        let unkeyed = Request::builder().method(Method::POST).uri("/plays").body(Body::from("a")).unwrap();
+        assert_eq!(harness.send(unkeyed).await.2, "run 3: a");
+    }
+
+    #[tokio::test]
+    async fn reusing_a_key_with_another_body_is_rejected() {
+        let harness = Harness::new(100);
+        harness.send(request("/plays", "k1", "a")).await;
+        assert_eq!(harness.send(request("/plays", "k1", "b")).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(harness.runs(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_while_running_get_a_conflict() {
+        let harness = Harness::new(100);
+        let app = harness.app.clone();
+        let first = tokio::spawn(async move { app.oneshot(request("/slow", "k1", "")).await.unwrap().status() });
+        // Lets the first request run until it waits in the handler
+        tokio::task::yield_now().await;
+
+        assert_eq!(harness.send(request("/slow", "k1", "")).await.0, StatusCode::CONFLICT);
+
+        harness.release.notify_one();
+        assert_eq!(first.await.unwrap(), StatusCode::CREATED);
+        assert_eq!(harness.send(request("/slow", "k1", "")).await, (StatusCode::CREATED, true, String::new()));
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_not_stored() {
+        let harness = Harness::new(100);
+        assert_eq!(harness.send(request("/flaky", "k1", "")).await.0, StatusCode::BAD_GATEWAY);
+        assert_eq!(harness.send(request("/flaky", "k1", "")).await, (StatusCode::CREATED, false, String::new()));
+        assert_eq!(harness.send(request("/flaky", "k1", "")).await, (StatusCode::CREATED, true, String::new()));
+        assert_eq!(harness.runs(), 2);
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_the_caller_and_route() {
+        let harness = Harness::new(100);
+        let from = |authorization: Option<&str>, ip: &str| {
+            let mut builder = post_with_key("/plays", "k1").extension(ClientIp(ip.parse::<IpAddr>().unwrap()));
+            if let Some(authorization) = authorization {
+                builder = builder.header(header::AUTHORIZATION, authorization);
+            }
+            builder.body(Body::from("a")).unwrap()
+        };
+
+        assert_eq!(harness.send(from(None, "203.0.113.1")).await.2, "run 1: a");
+        assert_eq!(harness.send(from(None, "203.0.113.2")).await.2, "run 2: a");
+        assert_eq!(harness.send(from(Some("Bearer one"), "203.0.113.1")).await.2, "run 3: a");
+        assert_eq!(harness.send(from(Some("Bearer two"), "203.0.113.1")).await.2, "run 4: a");
+        // The player token identifies the caller wherever they connect from
+        assert_eq!(harness.send(from(Some("Bearer one"), "203.0.113.9")).await, (StatusCode::CREATED, true, "run 3: a".to_string()));
+        assert_eq!(harness.send(from(None, "203.0.113.1")).await, (StatusCode::CREATED, true, "run 1: a".to_string()));
+        // The same key on another route is a different request
+        assert!(!harness.send(request("/flaky", "k1", "a")).await.1);
+    }
+
+    #[tokio::test]
+    async fn oldest_responses_are_dropped_at_capacity() {
+        let harness = Harness::new(2);
+        for key in ["k1", "k2", "k3"] {
+            harness.send(request("/plays", key, "a")).await;
+        }
+        assert_eq!(harness.send(request("/plays", "k3", "a")).await, (StatusCode::CREATED, true, "run 3: a".to_string()));
+        assert_eq!(harness.send(request("/plays", "k1", "a")).await, (StatusCode::CREATED, false, "run 4: a".to_string()));
+    }
+
+    #[tokio::test]
+    async fn malformed_keys_are_rejected() {
+        let harness = Harness::new(100);
+        assert_eq!(harness.send(request("/plays", "", "a")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(harness.send(request("/plays", &"k".repeat(MAX_KEY_LENGTH + 1), "a")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(harness.runs(), 0);
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_before_the_oldest_live_one() {
+        let start = Instant::now();
+        let ttl = Duration::from_secs(60);
+        let done = |at: Instant| Entry::Done {
+            fingerprint: Vec::new(),
+            stored_at: at,
+            status: StatusCode::OK,
+            content_type: None,
+            body: Bytes::new(),
+        };
+        let mut entries = Entries::default();
+        entries.insert(b"old".to_vec(), done(start));
+        entries.insert(b"new".to_vec(), done(start + Duration::from_secs(30)));
+        // Rewriting a key keeps it until its latest write expires
+        entries.insert(b"old".to_vec(), done(start + Duration::from_secs(40)));
+
+        entries.make_room(start + Duration::from_secs(65), ttl, 10);
+        assert_eq!(entries.by_key.len(), 2);
+        entries.make_room(start + Duration::from_secs(95), ttl, 10);
+        assert_eq!(entries.by_key.keys().collect::<Vec<_>>(), [b"old"]);
+        entries.make_room(start + Duration::from_secs(100), ttl, 10);
+        assert!(entries.by_key.is_empty() && entries.order.is_empty());
+    }
+}
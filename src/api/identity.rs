@@ -0,0 +1,234 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    Extension,
+};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::{env, fs, sync::Arc};
+use tracing::warn;
+
+#[derive(Debug, Deserialize)]
+struct PlayerClaims {
+    sub: String,
+}
+
+struct Verifier {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+/// Verifies player tokens issued by the main site. When no signing key is
+/// configured verification is disabled and the client-supplied key is trusted.
+#[derive(Clone, Default)]
+pub struct PlayerTokenVerifier {
+    verifier: Option<Arc<Verifier>>,
+}
+
+impl PlayerTokenVerifier {
+    /// Reads `PLAYER_TOKEN_HS256_SECRET` or an RS256 public key from
+    /// `PLAYER_TOKEN_RS256_PUBLIC_KEY` / `PLAYER_TOKEN_RS256_PUBLIC_KEY_FILE`, plus
+    /// the optional `PLAYER_TOKEN_ISSUER` and `PLAYER_TOKEN_AUDIENCE` claims to require.
+    pub fn from_env() -> Result<Self, String> {
+        let rs256_pem = match (env::var("PLAYER_TOKEN_RS256_PUBLIC_KEY"), env::var("PLAYER_TOKEN_RS256_PUBLIC_KEY_FILE")) {
+            (Ok(pem), _) => Some(pem),
+            (Err(_), Ok(path)) => Some(fs::read_to_string(&path).map_err(|err| format!("Failed to read {}: {}", path, err))?),
+            _ => None,
+        };
+
+        let (key, algorithm) = match (env::var("PLAYER_TOKEN_HS256_SECRET"), rs256_pem) {
+            (Ok(_), Some(_)) => return Err("Configure either an HS256 secret or an RS256 public key for player tokens, not both".to_string()),
+            (Ok(secret), None) => (DecodingKey::from_secret(secret.as_bytes()), Algorithm::HS256),
+            (Err(_), Some(pem)) => (
+                DecodingKey::from_rsa_pem(pem.as_bytes()).map_err(|err| format!("Invalid RS256 public key: {}", err))?,
+                Algorithm::RS256,
+            ),
+            (Err(_), None) => return Ok(Self::default()),
+        };
+
+        let mut validation = Validation::new(algorithm);
+        if let Ok(issuer) = env::var("PLAYER_TOKEN_ISSUER") {
+            validation.set_issuer(&[issuer]);
+        }
+        match env::var("PLAYER_TOKEN_AUDIENCE") {
+            Ok(audience) => validation.set_audience(&[audience]),
+            Err(_) => validation.validate_aud = false,
+        }
+
+        Ok(Self { verifier: Some(Arc::new(Verifier { key, validation })) })
+    }
+
+    /// Verifies HS256 tokens signed with `secret`, with no issuer or audience required.
+    #[cfg(test)]
+    pub(crate) fn hs256(secret: &str) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.validate_aud = false;
+        let verifier = Verifier { key: DecodingKey::from_secret(secret.as_bytes()), validation };
+        Self { verifier: Some(Arc::new(verifier)) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.verifier.is_some()
+    }
+
+    /// The player a request's bearer token was issued to, if tokens are enabled
+    /// and it carries a valid one.
+    pub fn verified_subject(&self, headers: &HeaderMap) -> Option<String> {
+        let verifier = self.verifier.as_ref()?;
+        verifier.verify(bearer_token(headers)?).ok()
+    }
+}
+
+impl Verifier {
+    /// The token's subject, or why it was rejected.
+    fn verify(&self, token: &str) -> Result<String, String> {
+        let claims = decode::<PlayerClaims>(token, &self.key, &self.validation)
+            .map_err(|err| err.to_string())?
+            .claims;
+
+        if claims.sub.is_empty() {
+            return Err("no subject".to_string());
+        }
+        Ok(claims.sub)
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// The player making a play request.
+///
+/// Holds the token subject when player tokens are enabled (requests without a
+/// valid `Authorization: Bearer` token are rejected with 401), and `None` otherwise.
+pub struct PlayerIdentity(Option<String>);
+
+impl PlayerIdentity {
+    /// Returns the verified subject, falling back to the key from the request body.
+    pub fn key_or(self, requested: Option<String>) -> Option<String> {
+        self.0.or(requested)
+    }
+
+    /// Whether this caller may read data for `key`: only with a token for that
+    /// player, so never when tokens are disabled.
+    pub fn can_access(&self, key: &str) -> bool {
+        self.0.as_deref() == Some(key)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PlayerIdentity {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(tokens) = Extension::<PlayerTokenVerifier>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let Some(verifier) = &tokens.verifier else {
+            return Ok(Self(None));
+        };
+
+        let token = bearer_token(&parts.headers).ok_or(StatusCode::UNAUTHORIZED)?;
+        verifier.verify(token).map(|sub| Self(Some(sub))).map_err(|reason| {
+            warn!("Rejected player token: {}", reason);
+            StatusCode::UNAUTHORIZED
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, routing::get, Router};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    /// Answers with the key a play would be recorded under, given `body-key` in the body.
+    fn app(tokens: PlayerTokenVerifier) -> Router {
+        Router::new()
+            .route("/play", get(|identity: PlayerIdentity| async move { identity.key_or(Some("body-key".to_string())).unwrap_or_default() }))
+            .layer(Extension(tokens))
+    }
+
+    fn token(claims: serde_json::Value, secret: &str) -> String {
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    fn expires_in(secs: i64) -> i64 {
+        chrono::Utc::now().timestamp() + secs
+    }
+
+    async fn play(tokens: PlayerTokenVerifier, authorization: Option<String>) -> (StatusCode, String) {
+        let mut request = Request::get("/play");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let response = app(tokens).oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn valid_tokens_name_the_player_over_the_body_key() {
+        let bearer = format!("Bearer {}", token(json!({ "sub": "alice", "exp": expires_in(60) }), "secret"));
+        assert_eq!(play(PlayerTokenVerifier::hs256("secret"), Some(bearer)).await, (StatusCode::OK, "alice".to_string()));
+    }
+
+    #[tokio::test]
+    async fn missing_expired_and_forged_tokens_are_rejected() {
+        let tokens = PlayerTokenVerifier::hs256("secret");
+        let rejected = [
+            None,
+            Some("alice".to_string()),
+            Some(format!("Bearer {}", token(json!({ "sub": "alice", "exp": expires_in(-3600) }), "secret"))),
+            Some(format!("Bearer {}", token(json!({ "sub": "alice", "exp": expires_in(60) }), "other-secret"))),
+            Some(format!("Bearer {}", token(json!({ "sub": "alice" }), "secret"))),
+            Some("Bearer not-a-token".to_string()),
+        ];
+        for authorization in rejected {
+            assert_eq!(play(tokens.clone(), authorization.clone()).await.0, StatusCode::UNAUTHORIZED, "{:?}", authorization);
+        }
+    }
+
+    #[tokio::test]
+    async fn tokens_without_a_subject_are_rejected() {
+        let bearer = format!("Bearer {}", token(json!({ "sub": "", "exp": expires_in(60) }), "secret"));
+        assert_eq!(play(PlayerTokenVerifier::hs256("secret"), Some(bearer)).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn without_tokens_the_body_key_is_used() {
+        let bearer = format!("Bearer {}", token(json!({ "sub": "alice", "exp": expires_in(60) }), "secret"));
+        assert_eq!(play(PlayerTokenVerifier::default(), Some(bearer)).await, (StatusCode::OK, "body-key".to_string()));
+        assert_eq!(play(PlayerTokenVerifier::default(), None).await, (StatusCode::OK, "body-key".to_string()));
+    }
+
+    #[test]
+    fn only_the_token_subject_can_access_player_data() {
+        assert!(PlayerIdentity(Some("alice".to_string())).can_access("alice"));
+        assert!(!PlayerIdentity(Some("alice".to_string())).can_access("bob"));
+        // With tokens disabled nobody is verified, so nobody gets in
+        assert!(!PlayerIdentity(None).can_access("alice"));
+        assert!(!PlayerIdentity(None).can_access(""));
+    }
+
+    #[test]
+    fn verified_subject_needs_enabled_tokens_and_a_valid_bearer() {
+        let mut headers = HeaderMap::new();
+        let bearer = format!("Bearer {}", token(json!({ "sub": "alice", "exp": expires_in(60) }), "secret"));
+        headers.insert(header::AUTHORIZATION, bearer.parse().unwrap());
+
+        assert_eq!(PlayerTokenVerifier::hs256("secret").verified_subject(&headers).as_deref(), Some("alice"));
+        assert_eq!(PlayerTokenVerifier::hs256("other-secret").verified_subject(&headers), None);
+        assert_eq!(PlayerTokenVerifier::default().verified_subject(&headers), None);
+        assert_eq!(PlayerTokenVerifier::hs256("secret").verified_subject(&HeaderMap::new()), None);
+    }
+}
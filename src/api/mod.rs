@@ -1,2 +1,10 @@
 pub mod routes;
-pub mod handlers;
\ No newline at end of file
+pub mod handlers;
+pub mod auth;
+pub mod identity;
+pub mod rate_limit;
+pub mod idempotency;
+pub mod extract;
+pub mod export;
+pub mod http_metrics;
+pub mod openapi;
\ No newline at end of file
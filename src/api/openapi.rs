@@ -0,0 +1,127 @@
+use axum::response::Json;
+use utoipa::{
+    openapi::{
+        path::PathItem,
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        OpenApi as OpenApiDocument,
+    },
+    Modify, OpenApi,
+};
+use crate::domain::models::{
+    ArchivedResult, BulkItemResult, BulkOperation, BulkRequest, BulkResponse, Campaign, DependencyStatus, GameType,
+    LimitPeriod, LimitPolicy, PlayerGameStatus, PlayerHistoryEntry, PlayerStatus, Readiness, SpinRequest, SpinResponse,
+    SpinResult, SpinResultPatch, Stats, StatsBucket, StatsPeriod, StatsSummary, WheelPrize, WheelRequest, WheelResponse,
+};
+use super::{export, handlers};
+
+/// Game-scoped result paths, which `/spin-results` mirrors for the spin game.
+const GAME_RESULTS_PATH: &str = "/games/{game}/results";
+const SPIN_RESULTS_PATH: &str = "/spin-results";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Notion CRUD API", description = "Spin and wheel games with results stored in Notion."),
+    paths(
+        handlers::get_root,
+        handlers::get_healthz,
+        handlers::get_readyz,
+        get_openapi,
+        handlers::spin_result,
+        handlers::wheel_result,
+        handlers::get_active_campaigns,
+        handlers::get_player_status,
+        handlers::get_player_history,
+        handlers::create_spin_result,
+        handlers::get_spin_results,
+        export::export_spin_results,
+        handlers::bulk_spin_results,
+        handlers::get_archived_spin_results,
+        handlers::update_spin_result,
+        handlers::patch_spin_result,
+        handlers::delete_spin_result,
+        handlers::fulfil_spin_result,
+        handlers::restore_spin_result,
+        handlers::get_stats,
+        handlers::reload_config,
+        handlers::get_metrics,
+    ),
+    components(schemas(
+        GameType, SpinResult, SpinResultPatch, ArchivedResult, BulkOperation, BulkRequest, BulkItemResult, BulkResponse,
+        SpinRequest, SpinResponse, WheelRequest, WheelResponse, WheelPrize, Campaign, LimitPeriod, LimitPolicy,
+        PlayerStatus, PlayerGameStatus, PlayerHistoryEntry, Stats, StatsBucket, StatsPeriod, StatsSummary,
+        Readiness, DependencyStatus, export::ExportFormat,
+    )),
+    modifiers(&SecuritySchemes, &SpinResultsAlias),
+    tags(
+        (name = "play", description = "Endpoints used by the game frontends"),
+        (name = "results", description = "Result management, needs an admin API key"),
+        (name = "admin", description = "Operations, needs an admin API key"),
+        (name = "health", description = "Liveness, readiness and this document"),
+    ),
+)]
+pub struct ApiDoc;
+
+/// This document, as JSON.
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "health",
+    responses((status = 200, description = "The OpenAPI document", content_type = "application/json")),
+)]
+pub async fn get_openapi() -> Json<OpenApiDocument> {
+    Json(ApiDoc::openapi())
+}
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Admin API key secret. Requests can instead be signed with `X-Api-Key-Id`, `X-Signature-Timestamp` and `X-Signature`."))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "player_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Player token, required when player tokens are enabled."))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Documents `/spin-results` as the spin game's copy of `/games/{game}/results`,
+/// without the `game` parameter and with its own operation ids.
+struct SpinResultsAlias;
+
+impl Modify for SpinResultsAlias {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let aliases: Vec<(String, PathItem)> = openapi
+            .paths
+            .paths
+            .iter()
+            .filter_map(|(path, item)| {
+                let rest = path.strip_prefix(GAME_RESULTS_PATH)?;
+                let mut item = item.clone();
+                for operation in item.operations.values_mut() {
+                    if let Some(parameters) = operation.parameters.as_mut() {
+                        parameters.retain(|parameter| parameter.name != "game");
+                    }
+                    operation.operation_id = operation.operation_id.take().map(|id| format!("{}_spin", id));
+                }
+                Some((format!("{}{}", SPIN_RESULTS_PATH, rest), item))
+            })
+            .collect();
+
+        openapi.paths.paths.extend(aliases);
+    }
+}
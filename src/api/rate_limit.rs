@@ -0,0 +1,470 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::stream::{self, StreamExt};
+use ipnet::IpNet;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::env;
+use tracing::warn;
+
+use super::identity::PlayerTokenVerifier;
+
+const MAX_KEYED_BODY_BYTES: usize = 64 * 1024;
+/// How often clients whose allowance has fully recovered are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A request rate such as `10/s` or `300/m`. The full amount may be used in a burst.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    emission_interval: Duration,
+    tolerance: Duration,
+}
+
+impl FromStr for Quota {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, unit) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("Rate must look like 10/s, got {:?}", s))?;
+        let count = count
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(|| format!("Rate count must be a positive integer, got {:?}", count))?;
+        let period = match unit.trim() {
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(3600),
+            other => return Err(format!("Rate unit must be s, m or h, got {:?}", other)),
+        };
+
+        let emission_interval = period / count;
+        Ok(Self {
+            emission_interval,
+            tolerance: period - emission_interval,
+        })
+    }
+}
+
+/// Generic cell rate algorithm state: the theoretical arrival time per client.
+#[derive(Default)]
+struct Gcra {
+    arrivals: HashMap<String, Instant>,
+    next_prune: Option<Instant>,
+}
+
+impl Gcra {
+    /// Returns the client's next arrival time if a request is allowed now, or how
+    /// long to wait when it exceeds the quota.
+    fn next_arrival(&self, client: &str, quota: Quota, now: Instant) -> Result<Instant, Duration> {
+        let tat = self.arrivals.get(client).copied().unwrap_or(now).max(now);
+        let allow_at = tat - quota.tolerance;
+        if allow_at > now {
+            return Err(allow_at - now);
+        }
+        Ok(tat + quota.emission_interval)
+    }
+
+    fn record(&mut self, client: String, tat: Instant, now: Instant) {
+        self.arrivals.insert(client, tat);
+        // At most one scan per interval, however many distinct clients show up
+        if self.next_prune.is_none_or(|next_prune| now >= next_prune) {
+            self.arrivals.retain(|_, tat| *tat > now);
+            self.next_prune = Some(now + PRUNE_INTERVAL);
+        }
+    }
+}
+
+/// The client address resolved from the connection and trusted proxies, for
+/// middleware that runs after the rate limiter.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Per-IP and per-player-key request limits applied to every route.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    per_ip: Option<Quota>,
+    per_key: Option<Quota>,
+    trusted_proxies: Arc<Vec<IpNet>>,
+    player_tokens: PlayerTokenVerifier,
+    state: Arc<Mutex<Gcra>>,
+}
+
+impl RateLimiter {
+    /// Reads `RATE_LIMIT_PER_IP`, `RATE_LIMIT_PER_KEY` and `TRUSTED_PROXIES`
+    /// (comma-separated addresses or CIDR ranges allowed to set `X-Forwarded-For`).
+    pub fn from_env() -> Result<Self, String> {
+        let quota = |name: &str| env::var(name).ok().map(|rate| rate.parse::<Quota>().map_err(|err| format!("{}: {}", name, err))).transpose();
+
+        let trusted_proxies = match env::var("TRUSTED_PROXIES") {
+            Ok(proxies) => proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| {
+                    proxy
+                        .parse::<IpNet>()
+                        .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|_| format!("TRUSTED_PROXIES: invalid address {:?}", proxy))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(_) => Vec::new(),
+        };
+
+        Ok(Self {
+            per_ip: quota("RATE_LIMIT_PER_IP")?,
+            per_key: quota("RATE_LIMIT_PER_KEY")?,
+            trusted_proxies: Arc::new(trusted_proxies),
+            player_tokens: PlayerTokenVerifier::default(),
+            state: Arc::default(),
+        })
+    }
+
+    /// Keys the per-player limit on the verified token subject when player tokens
+    /// are enabled, rather than on the `key` a client puts in the body.
+    pub fn with_player_tokens(mut self, player_tokens: PlayerTokenVerifier) -> Self {
+        self.player_tokens = player_tokens;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_ip.is_some() || self.per_key.is_some()
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Resolves the client address, walking `X-Forwarded-For` from the right for as
+    /// long as each hop was added by a trusted proxy.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+
+        let mut client = peer;
+        for hop in forwarded.into_iter().rev() {
+            client = hop;
+            if !self.is_trusted(hop) {
+                break;
+            }
+        }
+        client
+    }
+
+    fn check(&self, ip: Option<IpAddr>, key: Option<&str>) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        let mut clients = Vec::new();
+        if let (Some(quota), Some(ip)) = (self.per_ip, ip) {
+            clients.push((format!("ip:{}", ip), quota));
+        }
+        if let (Some(quota), Some(key)) = (self.per_key, key) {
+            clients.push((format!("key:{}", key), quota));
+        }
+
+        // Only count the request once it passes every applicable limit
+        let arrivals = clients
+            .iter()
+            .map(|(client, quota)| state.next_arrival(client, *quota, now))
+            .collect::<Result<Vec<_>, _>>()?;
+        for ((client, _), tat) in clients.into_iter().zip(arrivals) {
+            state.record(client, tat, now);
+        }
+        Ok(())
+    }
+}
+
+/// The `key` field of a JSON body, read whether or not the body has a
+/// `Content-Length`. Bodies larger than `MAX_KEYED_BODY_BYTES` (bulk admin
+/// requests) are passed on unread past that point and have no key.
+async fn body_key(request: Request) -> (Option<String>, Request) {
+    let headers = request.headers();
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let is_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok())
+        .is_some_and(|length| length > MAX_KEYED_BODY_BYTES);
+    if !is_json || is_large {
+        return (None, request);
+    }
+
+    let (parts, body) = request.into_parts();
+    let mut rest = body.into_data_stream();
+    let mut chunks = Vec::new();
+    let mut read = 0;
+    let failed = loop {
+        match rest.next().await {
+            Some(Ok(chunk)) => {
+                read += chunk.len();
+                chunks.push(chunk);
+                if read > MAX_KEYED_BODY_BYTES {
+                    break None;
+                }
+            }
+            // Left for the handler to report
+            Some(Err(err)) => break Some(err),
+            None => {
+                let body = chunks.concat();
+                let key = serde_json::from_slice::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|json| json["key"].as_str().map(str::to_string));
+                return (key, Request::from_parts(parts, Body::from(body)));
+            }
+        }
+    };
+
+    let body = stream::iter(chunks.into_iter().map(Ok))
+        .chain(stream::iter(failed.map(Err)))
+        .chain(rest);
+    (None, Request::from_parts(parts, Body::from_stream(body)))
+}
+
+pub async fn limit_requests(
+    State(limiter): State<RateLimiter>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| limiter.client_ip(addr.ip(), request.headers()));
+    if let Some(ip) = ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+
+    if !limiter.is_enabled() {
+        return next.run(request).await;
+    }
+
+    // With player tokens the body key is not trusted, so a request without a
+    // valid token (including admin requests) is limited by IP only
+    let (key, request) = if limiter.per_key.is_none() {
+        (None, request)
+    } else if limiter.player_tokens.is_enabled() {
+        (limiter.player_tokens.verified_subject(request.headers()), request)
+    } else {
+        body_key(request).await
+    };
+
+    if let Err(retry_after) = limiter.check(ip, key.as_deref()) {
+        warn!("Rate limit exceeded for {} {}", request.method(), request.uri().path());
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        return response;
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use axum::body::Bytes;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use tower::ServiceExt;
+
+    fn limiter(per_ip: Option<&str>, per_key: Option<&str>, trusted_proxies: &[&str]) -> RateLimiter {
+        RateLimiter {
+            per_ip: per_ip.map(|rate| rate.parse().unwrap()),
+            per_key: per_key.map(|rate| rate.parse().unwrap()),
+            trusted_proxies: Arc::new(trusted_proxies.iter().map(|net| net.parse().unwrap()).collect()),
+            player_tokens: PlayerTokenVerifier::default(),
+            state: Arc::default(),
+        }
+    }
+
+    fn forwarded_for(hops: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_str(hops).unwrap());
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn quota_parses_count_and_unit() {
+        let quota: Quota = "4/s".parse().unwrap();
+        assert_eq!(quota.emission_interval, Duration::from_millis(250));
+        assert_eq!(quota.tolerance, Duration::from_millis(750));
+        assert_eq!(" 300 / m ".parse::<Quota>().unwrap().emission_interval, Duration::from_millis(200));
+
+        for rate in ["", "10", "0/s", "-1/s", "ten/s", "10/d"] {
+            assert!(rate.parse::<Quota>().is_err(), "{:?}", rate);
+        }
+    }
+
+    #[test]
+    fn gcra_allows_a_burst_then_spaces_requests() {
+        let quota: Quota = "3/s".parse().unwrap();
+        let mut gcra = Gcra::default();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            let tat = gcra.next_arrival("ip:a", quota, start).unwrap();
+            gcra.record("ip:a".to_string(), tat, start);
+        }
+        let wait = gcra.next_arrival("ip:a", quota, start).unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= quota.emission_interval, "{:?}", wait);
+
+        // Other clients have their own allowance
+        assert!(gcra.next_arrival("ip:b", quota, start).is_ok());
+        // One emission interval later a single request is allowed again
+        let later = start + quota.emission_interval;
+        let tat = gcra.next_arrival("ip:a", quota, later).unwrap();
+        gcra.record("ip:a".to_string(), tat, later);
+        assert!(gcra.next_arrival("ip:a", quota, later).is_err());
+    }
+
+    #[test]
+    fn gcra_forgets_recovered_clients() {
+        let quota: Quota = "1/s".parse().unwrap();
+        let mut gcra = Gcra::default();
+        let start = Instant::now();
+
+        let tat = gcra.next_arrival("ip:a", quota, start).unwrap();
+        gcra.record("ip:a".to_string(), tat, start);
+        assert_eq!(gcra.arrivals.len(), 1);
+
+        // Within the prune interval nothing is scanned
+        let soon = start + Duration::from_secs(2);
+        let tat = gcra.next_arrival("ip:b", quota, soon).unwrap();
+        gcra.record("ip:b".to_string(), tat, soon);
+        assert_eq!(gcra.arrivals.len(), 2);
+
+        let later = start + PRUNE_INTERVAL + Duration::from_secs(2);
+        let tat = gcra.next_arrival("ip:c", quota, later).unwrap();
+        gcra.record("ip:c".to_string(), tat, later);
+        assert_eq!(gcra.arrivals.keys().collect::<Vec<_>>(), ["ip:c"]);
+    }
+
+    #[test]
+    fn rejected_requests_do_not_count() {
+        let limiter = limiter(Some("1/h"), Some("2/h"), &[]);
+        let client = Some(ip("203.0.113.7"));
+
+        assert!(limiter.check(client, Some("p1")).is_ok());
+        // Over the IP limit, so the key keeps its second play
+        assert!(limiter.check(client, Some("p1")).is_err());
+        assert!(limiter.check(Some(ip("203.0.113.8")), Some("p1")).is_ok());
+        assert!(limiter.check(Some(ip("203.0.113.9")), Some("p1")).is_err());
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let limiter = limiter(None, None, &["10.0.0.0/8"]);
+        let headers = forwarded_for("198.51.100.1");
+        assert_eq!(limiter.client_ip(ip("203.0.113.7"), &headers), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_for_is_walked_through_trusted_proxies() {
+        let limiter = limiter(None, None, &["10.0.0.0/8", "192.0.2.1/32"]);
+
+        // The left-most hops are whatever the client sent and cannot be trusted
+        let headers = forwarded_for("198.51.100.99, 203.0.113.7, 192.0.2.1");
+        assert_eq!(limiter.client_ip(ip("10.1.2.3"), &headers), ip("203.0.113.7"));
+
+        assert_eq!(limiter.client_ip(ip("10.1.2.3"), &forwarded_for("203.0.113.7")), ip("203.0.113.7"));
+        assert_eq!(limiter.client_ip(ip("10.1.2.3"), &HeaderMap::new()), ip("10.1.2.3"));
+        // Unparseable hops are skipped
+        assert_eq!(limiter.client_ip(ip("10.1.2.3"), &forwarded_for("203.0.113.7, junk")), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn every_forwarded_for_header_is_read() {
+        let limiter = limiter(None, None, &["10.0.0.0/8"]);
+        let mut headers = forwarded_for("203.0.113.7");
+        headers.append("x-forwarded-for", HeaderValue::from_static("10.0.0.2"));
+        assert_eq!(limiter.client_ip(ip("10.0.0.1"), &headers), ip("203.0.113.7"));
+    }
+
+    /// Posts `body` to a route behind `limiter`, as one chunk without a
+    /// `Content-Length`, and returns the status and how many bytes the handler got.
+    async fn post_chunked(limiter: &RateLimiter, body: Vec<u8>, token: Option<&str>) -> (StatusCode, usize) {
+        let app = Router::new()
+            .route("/play", post(|body: Bytes| async move { body.len().to_string() }))
+            .layer(middleware::from_fn_with_state(limiter.clone(), limit_requests));
+        let mut request = Request::post("/play").header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let chunks = stream::iter([Ok::<_, std::io::Error>(Bytes::from(body))]);
+        let response = app.oneshot(request.body(Body::from_stream(chunks)).unwrap()).await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).parse().unwrap_or(0))
+    }
+
+    fn play(key: &str) -> Vec<u8> {
+        format!(r#"{{"key": "{}"}}"#, key).into_bytes()
+    }
+
+    fn token(sub: &str, exp_offset: i64) -> String {
+        let claims = serde_json::json!({ "sub": sub, "exp": chrono::Utc::now().timestamp() + exp_offset });
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn chunked_bodies_are_keyed() {
+        let limiter = limiter(None, Some("1/h"), &[]);
+
+        assert_eq!(post_chunked(&limiter, play("p1"), None).await, (StatusCode::OK, play("p1").len()));
+        assert_eq!(post_chunked(&limiter, play("p1"), None).await.0, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(post_chunked(&limiter, play("p2"), None).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn large_chunked_bodies_pass_through_whole_and_unkeyed() {
+        let limiter = limiter(None, Some("1/h"), &[]);
+        let mut large = br#"{"key": "p1", "padding": ""#.to_vec();
+        large.resize(MAX_KEYED_BODY_BYTES * 2, b' ');
+
+        for _ in 0..2 {
+            assert_eq!(post_chunked(&limiter, large.clone(), None).await, (StatusCode::OK, large.len()));
+        }
+    }
+
+    #[tokio::test]
+    async fn player_tokens_key_on_the_subject_not_the_body() {
+        let limiter = limiter(None, Some("1/h"), &[]).with_player_tokens(PlayerTokenVerifier::hs256("secret"));
+
+        assert_eq!(post_chunked(&limiter, play("p1"), Some(&token("alice", 60))).await.0, StatusCode::OK);
+        // A fresh token, or a different key in the body, is still the same player
+        assert_eq!(post_chunked(&limiter, play("p2"), Some(&token("alice", 120))).await.0, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(post_chunked(&limiter, play("p1"), Some(&token("bob", 60))).await.0, StatusCode::OK);
+        // Without a valid token the body key is not trusted, so there is nothing to key on
+        for token in [None, Some("not-a-token".to_string()), Some(token("alice", -3600))] {
+            assert_eq!(post_chunked(&limiter, play("p1"), token.as_deref()).await.0, StatusCode::OK);
+        }
+    }
+}
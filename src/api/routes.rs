@@ -1,56 +1,172 @@
 use axum::{
     Router,
-    routing::{post, get, put, delete},
+    Extension,
+    middleware,
+    routing::{post, get, put, patch, delete},
 };
-use tower_http::cors::{CorsLayer, Any};
-use crate::application::services::NotionService;
-use crate::infrastructure::notion::NotionClient;
-use std::env;
-use http::{HeaderValue, Method};
+use axum::{body::Body, http::Request};
+use tower_http::{
+    cors::{CorsLayer, Any},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    timeout::TimeoutLayer,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{Level, Span};
+use super::handlers::AppService;
+use crate::domain::models::GameType;
+use super::auth::{self, ApiKeyStore};
+use super::identity::PlayerTokenVerifier;
+use super::rate_limit::{self, RateLimiter};
+use super::idempotency::{self, IdempotencyStore};
+use super::http_metrics;
+use super::openapi::{self, ApiDoc};
+use utoipa::OpenApi;
+use utoipa_redoc::{Redoc, Servable};
+use http::Method;
+use crate::config::Config;
 
-pub fn create_router(service: NotionService<NotionClient>) -> Router {
+pub fn create_router(
+    service: AppService,
+    config: &Config,
+    api_keys: ApiKeyStore,
+    player_tokens: PlayerTokenVerifier,
+    rate_limiter: RateLimiter,
+    idempotency: IdempotencyStore,
+) -> Router {
     let cors = CorsLayer::new()
         .allow_methods([
             Method::GET,
             Method::POST,
             Method::PUT,
+            Method::PATCH,
             Method::DELETE,
             Method::OPTIONS,
         ]);
     
-    let cors = match env::var("ALLOWED_ORIGINS") {
-        Ok(origins) => {
-            let origins: Vec<HeaderValue> = origins
-                .split(',')
-                .filter_map(|origin| origin.trim().parse().ok())
-                .collect();
-            
-            if origins.is_empty() {
-                cors.allow_origin(Any)
-            } else {
-                origins.iter().fold(cors, |cors, origin| {
-                    cors.allow_origin(origin.clone())
-                })
-            }
-        },
-        Err(_) => {
-            cors.allow_origin(Any)
-        }
+    let origins = config.allowed_origins();
+    let cors = if origins.is_empty() {
+        cors.allow_origin(Any)
+    } else {
+        cors.allow_origin(origins)
     }
     .allow_headers(Any);
 
     Router::new()
+        .merge(play_routes(player_tokens.clone(), idempotency.clone()))
+        .merge(admin_routes(api_keys.clone(), idempotency.clone()))
+        .layer(TimeoutLayer::new(config.request_timeout()))
+        // Bulk runs and exports are paced by Notion's rate limit, so they get their own timeout
+        .merge(long_admin_routes(api_keys, idempotency).layer(TimeoutLayer::new(config.long_request_timeout())))
+        .layer(middleware::from_fn_with_state(rate_limiter.with_player_tokens(player_tokens), rate_limit::limit_requests))
+        .merge(probe_routes().merge(docs_routes()).layer(TimeoutLayer::new(config.request_timeout())))
+        .layer(middleware::from_fn(http_metrics::track_requests))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        // Keeps an incoming X-Request-Id, otherwise generates one, and echoes it in the response
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(cors)
+        .with_state(service)
+}
+
+/// Span wrapping everything logged while handling a request, including its Notion calls.
+fn request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!("request", request_id, method = %request.method(), path = %request.uri().path())
+}
+
+/// Health checks for load balancers and orchestrators, exempt from rate limits.
+fn probe_routes() -> Router<AppService> {
+    Router::new()
+        .route("/healthz", get(super::handlers::get_healthz))
+        .route("/readyz", get(super::handlers::get_readyz))
+}
+
+/// The OpenAPI document and a Redoc page rendering it.
+fn docs_routes() -> Router<AppService> {
+    Router::new()
+        .route("/openapi.json", get(openapi::get_openapi))
+        .merge(Redoc::with_url("/docs", ApiDoc::openapi()))
+}
+
+/// Public endpoints used by the game frontends.
+fn play_routes(player_tokens: PlayerTokenVerifier, idempotency: IdempotencyStore) -> Router<AppService> {
+    let routes = Router::new()
         .route("/", get(super::handlers::get_root))
-        .route("/spin-results", post(super::handlers::create_spin_result))
-        .route("/spin-results", get(super::handlers::get_spin_results))
-        .route("/spin-results/:page_id", put(super::handlers::update_spin_result))
-        .route("/spin-results/:page_id", delete(super::handlers::delete_spin_result))
-        .route("/spin-result", post(|state, json| async move {
-            super::handlers::spin_result(state, json).await
+        .route("/spin-result", post(|state, identity, json| async move {
+            super::handlers::spin_result(state, identity, json).await
         }))
-        .route("/wheel-result", post(|state, json| async move {
-            super::handlers::wheel_result(state, json).await
+        .route("/wheel-result", post(|state, identity, json| async move {
+            super::handlers::wheel_result(state, identity, json).await
         }))
-        .layer(cors)
-        .with_state(service)
-} 
\ No newline at end of file
+        .route("/campaigns/active", get(super::handlers::get_active_campaigns))
+        .route_layer(middleware::from_fn_with_state(idempotency, idempotency::replay_idempotent));
+
+    // Without player tokens anyone could read any player's data by guessing their key
+    let routes = if player_tokens.is_enabled() {
+        routes.merge(player_routes())
+    } else {
+        routes
+    };
+    routes.layer(Extension(player_tokens))
+}
+
+/// A player's own status and history, only mounted when player tokens are enabled.
+fn player_routes() -> Router<AppService> {
+    Router::new()
+        .route("/players/:key/status", get(super::handlers::get_player_status))
+        .route("/players/:key/history", get(super::handlers::get_player_history))
+}
+
+/// Result management endpoints, only reachable with an admin API key.
+///
+/// `/games/:game/results` serves every game; `/spin-results` is kept as the
+/// original spin-only path.
+fn admin_routes(api_keys: ApiKeyStore, idempotency: IdempotencyStore) -> Router<AppService> {
+    let routes = Router::new()
+        .nest("/spin-results", result_routes().layer(Extension(GameType::Spin)))
+        .nest("/games/:game/results", result_routes())
+        .route("/stats", get(super::handlers::get_stats))
+        .route("/admin/reload", post(super::handlers::reload_config))
+        .route("/metrics", get(super::handlers::get_metrics));
+    require_admin(routes, api_keys, idempotency)
+}
+
+/// Admin endpoints that walk many Notion pages or run many Notion calls.
+fn long_admin_routes(api_keys: ApiKeyStore, idempotency: IdempotencyStore) -> Router<AppService> {
+    let routes = Router::new()
+        .nest("/spin-results", long_result_routes().layer(Extension(GameType::Spin)))
+        .nest("/games/:game/results", long_result_routes());
+    require_admin(routes, api_keys, idempotency)
+}
+
+fn require_admin(routes: Router<AppService>, api_keys: ApiKeyStore, idempotency: IdempotencyStore) -> Router<AppService> {
+    routes
+        .route_layer(middleware::from_fn_with_state(idempotency, idempotency::replay_idempotent))
+        .route_layer(middleware::from_fn_with_state(api_keys, auth::require_api_key))
+}
+
+fn long_result_routes() -> Router<AppService> {
+    Router::new()
+        .route("/export", get(super::export::export_spin_results))
+        .route("/bulk", post(super::handlers::bulk_spin_results))
+}
+
+fn result_routes() -> Router<AppService> {
+    Router::new()
+        .route("/", post(super::handlers::create_spin_result))
+        .route("/", get(super::handlers::get_spin_results))
+        .route("/trash", get(super::handlers::get_archived_spin_results))
+        .route("/:page_id", put(super::handlers::update_spin_result))
+        .route("/:page_id", patch(super::handlers::patch_spin_result))
+        .route("/:page_id", delete(super::handlers::delete_spin_result))
+        .route("/:page_id/fulfil", post(super::handlers::fulfil_spin_result))
+        .route("/:page_id/restore", post(super::handlers::restore_spin_result))
+}
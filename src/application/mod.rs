@@ -1 +1,2 @@
-pub mod services;
\ No newline at end of file
+pub mod services;
+pub mod stats;
@@ -1,31 +1,653 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::{self, StreamExt};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration as StdDuration, Instant};
+use tokio_util::task::TaskTracker;
+use validator::Validate;
+use tracing::{debug, info, warn, Instrument};
 use crate::domain::{
-    models::{SpinResult, GameType},
+    models::{
+        normalize_page_id, SpinResult, SpinResultPatch, ArchivedResult, GameType, EntryFilter, EntryPage,
+        BulkOperation, BulkItemResult, BulkResponse, PlayerStatus, PlayerGameStatus, PlayerHistoryEntry, Stats,
+        LimitPolicy, LimitPeriod, GameSettings, Campaign, DependencyStatus, Readiness,
+    },
     repository::{NotionRepository, Error},
 };
+use crate::metrics;
+use super::stats::{self, StatsCache, StatsQuery};
+
+/// Bulk operations in flight at once. The Notion client throttles the actual
+/// request rate, this only bounds how many are queued behind it.
+const BULK_CONCURRENCY: usize = 4;
+const DEFAULT_STATS_CACHE_TTL: StdDuration = StdDuration::from_secs(60);
+const DEFAULT_READINESS_CACHE_TTL: StdDuration = StdDuration::from_secs(10);
 
 #[derive(Clone)]
 pub struct NotionService<R: NotionRepository + Clone> {
     repository: R,
+    // Swapped whole on reload, so a request sees either the old settings or the new ones
+    settings: Arc<RwLock<Arc<GameSettings>>>,
+    stats_cache: Arc<StatsCache>,
+    // One lock per page being fulfilled, so two fulfils of the same prize run one after the other
+    fulfilments: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+    // Notion writes for plays that have been drawn. Each runs as its own task so a
+    // dropped connection cannot cancel it half way, and shutdown waits for them.
+    writes: TaskTracker,
+    // The last readiness check. Held while checking so concurrent probes wait for one result.
+    readiness: Arc<tokio::sync::Mutex<Option<(Instant, Readiness)>>>,
+    readiness_ttl: StdDuration,
 }
 
-impl<R: NotionRepository + Clone> NotionService<R> {
-    pub fn new(repository: R) -> Self {
-        Self { repository }
+impl<R: NotionRepository + Clone + 'static> NotionService<R> {
+    pub fn new(repository: R, settings: GameSettings) -> Self {
+        Self {
+            repository,
+            settings: Arc::new(RwLock::new(Arc::new(settings))),
+            stats_cache: Arc::new(StatsCache::new(DEFAULT_STATS_CACHE_TTL)),
+            fulfilments: Arc::default(),
+            writes: TaskTracker::new(),
+            readiness: Arc::default(),
+            readiness_ttl: DEFAULT_READINESS_CACHE_TTL,
+        }
+    }
+
+    /// How long computed stats are reused before being worked out again.
+    pub fn with_stats_cache_ttl(mut self, ttl: StdDuration) -> Self {
+        self.stats_cache = Arc::new(StatsCache::new(ttl));
+        self
+    }
+
+    /// How long a readiness check is reused before Notion is asked again.
+    pub fn with_readiness_cache_ttl(mut self, ttl: StdDuration) -> Self {
+        self.readiness_ttl = ttl;
+        self
+    }
+
+    /// The game settings currently in force.
+    pub fn settings(&self) -> Arc<GameSettings> {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Replaces the game settings for every request that starts from now on.
+    pub fn apply_settings(&self, settings: GameSettings) {
+        *self.settings.write().unwrap() = Arc::new(settings);
+        // Prize labels may have changed
+        self.stats_cache.clear();
+        info!("Applied new game settings");
+    }
+
+    pub async fn create_spin_result(&self, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        self.record_play(spin_result, game_type, None).await
+    }
+
+    /// Records a play made as part of a campaign, tagged with its id, under the
+    /// campaign's limit and in its database.
+    pub async fn create_campaign_result(&self, spin_result: SpinResult, campaign: &Campaign) -> Result<SpinResult, Error> {
+        let spin_result = SpinResult {
+            campaign: Some(campaign.id.clone()),
+            ..spin_result
+        };
+        self.record_play(spin_result, campaign.game, Some(campaign)).await
+    }
+
+    async fn record_play(&self, spin_result: SpinResult, game_type: GameType, campaign: Option<&Campaign>) -> Result<SpinResult, Error> {
+        if self.has_reached_limit(&spin_result.key, game_type, campaign).await? {
+            warn!(
+                "Play limit reached for key: {} with game type: {:?}",
+                spin_result.key, game_type
+            );
+            metrics::record_limit_rejection(game_type);
+            return Err(Error::SpinLimitReached);
+        }
+
+        let repository = self.repository.clone();
+        let database_id = campaign.and_then(|campaign| campaign.database_id.clone());
+        // Runs in the request's span so its Notion calls are logged with the request id
+        let write = self.writes.spawn(async move {
+            match database_id {
+                Some(database_id) => repository.create_entry_in(&database_id, spin_result, game_type).await,
+                None => repository.create_entry(spin_result, game_type).await,
+            }
+        }.in_current_span());
+        write
+            .await
+            .unwrap_or_else(|err| Err(Error::NotionApi(format!("Recording the play failed: {}", err))))
+    }
+
+    /// Waits for plays still being written to Notion. Used on shutdown once the
+    /// server has stopped taking requests.
+    pub async fn flush_writes(&self) {
+        self.writes.close();
+        if !self.writes.is_empty() {
+            info!("Waiting for {} plays still being written to Notion", self.writes.len());
+        }
+        self.writes.wait().await;
+    }
+
+    /// Campaigns running right now, for the frontends.
+    pub fn active_campaigns(&self) -> Vec<Campaign> {
+        self.settings().active_campaigns(Utc::now())
+    }
+
+    /// The campaign a play names, provided it belongs to `game_type` and is running now.
+    pub fn running_campaign(&self, id: &str, game_type: GameType) -> Result<Campaign, Error> {
+        let settings = self.settings();
+        let campaign = settings
+            .campaign(id)
+            .filter(|campaign| campaign.game == game_type)
+            .ok_or_else(|| Error::UnknownCampaign(id.to_string()))?;
+
+        let now = Utc::now();
+        if now < campaign.starts_at {
+            return Err(Error::CampaignNotActive(format!("Campaign {} has not started yet, it starts at {}", campaign.id, campaign.starts_at.to_rfc3339())));
+        }
+        if now >= campaign.ends_at {
+            return Err(Error::CampaignNotActive(format!("Campaign {} has ended, it ended at {}", campaign.id, campaign.ends_at.to_rfc3339())));
+        }
+        Ok(campaign.clone())
     }
 
-    pub async fn create_spin_result(&self, spin_result: SpinResult, game_type: GameType) -> Result<(), Error> {
-        self.repository.create_entry(spin_result, game_type).await
+    pub async fn get_spin_results(&self, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        self.repository.find_entries(game_type, filter).await
     }
 
-    pub async fn get_spin_results(&self, game_type: GameType) -> Result<Vec<SpinResult>, Error> {
-        self.repository.get_entries(game_type).await
+    /// One page of matching results, for walking large result sets without loading them all.
+    pub async fn page_spin_results(&self, game_type: GameType, filter: &EntryFilter, cursor: Option<&str>) -> Result<EntryPage, Error> {
+        self.repository.find_entries_page(game_type, filter, cursor).await
     }
 
-    pub async fn update_spin_result(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<(), Error> {
-        self.repository.update_entry(page_id, spin_result, game_type).await
+    pub async fn update_spin_result(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        let page_id = Self::parse_page_id(page_id)?;
+        self.repository.update_entry(&page_id, spin_result, game_type).await
+    }
+
+    /// Changes only the fields set in `patch`, keeping the rest of the stored result.
+    pub async fn patch_spin_result(&self, page_id: &str, patch: SpinResultPatch, game_type: GameType) -> Result<SpinResult, Error> {
+        let page_id = Self::parse_page_id(page_id)?;
+        if patch.is_empty() {
+            return self.repository.get_entry(&page_id, game_type).await;
+        }
+        self.repository.patch_entry(&page_id, patch, game_type).await
+    }
+
+    /// Marks a prize as paid out, recording who did it and when.
+    pub async fn fulfil_spin_result(&self, page_id: &str, fulfilled_by: &str, game_type: GameType) -> Result<SpinResult, Error> {
+        let page_id = Self::parse_page_id(page_id)?;
+        // Held until the patch is written, so a concurrent fulfil sees `checked` already set
+        let _fulfilling = self.lock_fulfilment(&page_id).await;
+        let spin_result = self.repository.get_entry(&page_id, game_type).await?;
+        if spin_result.checked {
+            warn!("Result {} for game type {:?} has already been fulfilled", page_id, game_type);
+            return Err(Error::AlreadyFulfilled);
+        }
+
+        let patch = SpinResultPatch {
+            checked: Some(true),
+            fulfilled_by: Some(fulfilled_by.to_string()),
+            fulfilled_at: Some(Utc::now().to_rfc3339()),
+            ..Default::default()
+        };
+
+        info!("Result {} for game type {:?} fulfilled by {}", page_id, game_type, fulfilled_by);
+        self.repository.patch_entry(&page_id, patch, game_type).await
+    }
+
+    async fn lock_fulfilment(&self, page_id: &str) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = {
+            let mut fulfilments = self.fulfilments.lock().unwrap();
+            // Drop locks nobody holds or waits on; only fulfils in progress are kept
+            fulfilments.retain(|_, lock| Arc::strong_count(lock) > 1);
+            fulfilments.entry(page_id.to_string()).or_default().clone()
+        };
+        lock.lock_owned().await
     }
 
     pub async fn delete_spin_result(&self, page_id: &str, game_type: GameType) -> Result<(), Error> {
-        self.repository.delete_entry(page_id, game_type).await
+        let page_id = Self::parse_page_id(page_id)?;
+        self.repository.delete_entry(&page_id, game_type).await
+    }
+
+    pub async fn restore_spin_result(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error> {
+        let page_id = Self::parse_page_id(page_id)?;
+        self.repository.restore_entry(&page_id, game_type).await
+    }
+
+    /// Results archived recently through the API, newest first.
+    pub async fn get_archived_spin_results(&self, game_type: GameType) -> Result<Vec<ArchivedResult>, Error> {
+        self.repository.get_archived_entries(game_type).await
+    }
+
+    /// Runs a batch of operations with bounded concurrency and reports each outcome.
+    pub async fn run_bulk(&self, operations: Vec<BulkOperation>, game_type: GameType) -> BulkResponse {
+        info!("Running {} bulk operations for game type: {:?}", operations.len(), game_type);
+
+        let mut results: Vec<BulkItemResult> = stream::iter(operations.into_iter().enumerate())
+            .map(|(index, operation)| async move {
+                let op = operation.name();
+                let target = match &operation {
+                    BulkOperation::Create { .. } => None,
+                    BulkOperation::Update { page_id, .. } | BulkOperation::Archive { page_id } => Some(page_id.clone()),
+                };
+                // Invalid items fail on their own rather than being sent to Notion
+                let outcome = match operation {
+                    BulkOperation::Create { result } => match result.validate() {
+                        Ok(()) => self.create_spin_result(result, game_type).await.map(|created| created.page_id),
+                        Err(errors) => Err(errors.into()),
+                    },
+                    BulkOperation::Update { page_id, patch } => match patch.validate() {
+                        Ok(()) => self.patch_spin_result(&page_id, patch, game_type).await.map(|updated| updated.page_id),
+                        Err(errors) => Err(errors.into()),
+                    },
+                    BulkOperation::Archive { page_id } => self.delete_spin_result(&page_id, game_type).await.map(|_| Some(page_id)),
+                };
+
+                match outcome {
+                    Ok(page_id) => BulkItemResult { index, op, success: true, page_id, error: None },
+                    Err(err) => BulkItemResult { index, op, success: false, page_id: target, error: Some(err.to_string()) },
+                }
+            })
+            .buffer_unordered(BULK_CONCURRENCY)
+            .collect()
+            .await;
+        results.sort_by_key(|result| result.index);
+
+        let succeeded = results.iter().filter(|result| result.success).count();
+        let failed = results.len() - succeeded;
+        info!("Bulk run for game type {:?} finished: {} succeeded, {} failed", game_type, succeeded, failed);
+
+        BulkResponse { succeeded, failed, results }
+    }
+
+    /// Remaining plays, reset time and bonus balance for every game.
+    pub async fn player_status(&self, key: &str) -> Result<PlayerStatus, Error> {
+        let now = Utc::now();
+        let settings = self.settings();
+        let exempt = settings.is_exempt(key);
+        let remaining = |policy: &LimitPolicy, used: usize| if exempt { policy.max as usize } else { (policy.max as usize).saturating_sub(used) };
+
+        let mut games = Vec::new();
+        for game_type in GameType::ALL {
+            let policy = settings.limit_policy(game_type);
+            let plays = self.plays_in_window(key, game_type, &policy, now, None).await?;
+            let used = plays.len();
+
+            // Campaigns counting plays apart from the game's limit give extra plays;
+            // one sharing both its limit and database with the game gives none
+            let mut bonus_balance = 0;
+            for campaign in settings.active_campaigns(now) {
+                if campaign.game != game_type || (campaign.limit.is_none() && campaign.database_id.is_none()) {
+                    continue;
+                }
+                let campaign_policy = Self::limit_policy(&settings, game_type, Some(&campaign));
+                let campaign_plays = self.plays_in_window(key, game_type, &campaign_policy, now, Some(&campaign)).await?;
+                bonus_balance += remaining(&campaign_policy, campaign_plays.len());
+            }
+
+            games.push(PlayerGameStatus {
+                game: game_type,
+                period: policy.period,
+                limit: policy.max,
+                used,
+                remaining: remaining(&policy, used),
+                bonus_balance,
+                resets_at: Self::resets_at(&policy, &plays, now).map(|resets_at| resets_at.to_rfc3339()),
+            });
+        }
+
+        Ok(PlayerStatus { key: key.to_string(), exempt, games })
+    }
+
+    /// The player's most recent outcomes, newest first, for one game or all of them.
+    pub async fn player_history(&self, key: &str, game_type: Option<GameType>, limit: usize) -> Result<Vec<PlayerHistoryEntry>, Error> {
+        let filter = EntryFilter {
+            key: Some(key.to_string()),
+            ..Default::default()
+        };
+
+        let mut history = Vec::new();
+        for game_type in game_type.map_or(GameType::ALL.to_vec(), |game_type| vec![game_type]) {
+            let spin_results = self.repository.find_entries(game_type, &filter).await?;
+            history.extend(spin_results.into_iter().map(|spin_result| {
+                let played_at = spin_result.played_at();
+                let entry = PlayerHistoryEntry {
+                    game: game_type,
+                    datetime: spin_result.datetime,
+                    number: spin_result.number,
+                    is_win: spin_result.is_win,
+                    fulfilled: spin_result.checked,
+                };
+                (played_at, entry)
+            }));
+        }
+
+        history.sort_by_key(|(played_at, _)| Reverse(*played_at));
+        Ok(history.into_iter().take(limit).map(|(_, entry)| entry).collect())
+    }
+
+    /// Whether the Notion token is accepted and every game and campaign database
+    /// can be read, checked at most once per cache TTL.
+    pub async fn readiness(&self) -> Readiness {
+        let mut cached = self.readiness.lock().await;
+        if let Some((checked_at, readiness)) = cached.as_ref() {
+            if checked_at.elapsed() < self.readiness_ttl {
+                return readiness.clone();
+            }
+        }
+
+        let settings = self.settings();
+        let games = GameType::ALL.iter().map(|game_type| async move {
+            DependencyStatus::new(format!("database:{}", game_type.as_str()), self.repository.check_database(*game_type).await)
+        });
+        let campaigns = settings.campaigns.iter().filter_map(|campaign| {
+            let database_id = campaign.database_id.as_deref()?;
+            Some(async move {
+                DependencyStatus::new(format!("campaign:{}", campaign.id), self.repository.check_database_in(database_id).await)
+            })
+        });
+
+        let mut dependencies = vec![DependencyStatus::new("notion_token", self.repository.check_token().await)];
+        dependencies.extend(futures::future::join_all(games).await);
+        dependencies.extend(futures::future::join_all(campaigns).await);
+
+        let readiness = Readiness {
+            ready: dependencies.iter().all(|dependency| dependency.ok),
+            dependencies,
+            checked_at: Utc::now().to_rfc3339(),
+        };
+        if !readiness.ready {
+            warn!("Not ready: {:?}", readiness.dependencies.iter().filter(|dependency| !dependency.ok).collect::<Vec<_>>());
+        }
+        *cached = Some((Instant::now(), readiness.clone()));
+        readiness
+    }
+
+    /// Play, result, win, prize and fulfilment counts for one game or all of them,
+    /// from the play counters and stored results. Reuses a recent answer to the same query.
+    pub async fn stats(&self, query: StatsQuery) -> Result<Stats, Error> {
+        if let Some(cached) = self.stats_cache.get(&query) {
+            debug!("Serving cached stats for {:?}", query);
+            return Ok(cached);
+        }
+
+        let filter = EntryFilter {
+            from: query.from,
+            to: query.to,
+            ..Default::default()
+        };
+
+        let mut results = Vec::new();
+        let mut plays = Vec::new();
+        for game_type in query.game.map_or(GameType::ALL.to_vec(), |game_type| vec![game_type]) {
+            let spin_results = self.repository.find_entries(game_type, &filter).await?;
+            results.extend(spin_results.into_iter().map(|spin_result| (game_type, spin_result)));
+            plays.extend(self.repository.play_counts(game_type, query.from, query.to).await?);
+        }
+
+        let computed = stats::summarize(&query, &results, &plays, &self.settings());
+        self.stats_cache.insert(query, computed.clone());
+        Ok(computed)
+    }
+
+    /// Counts a play, won or lost, for `/stats`. A play that cannot be counted still goes ahead.
+    pub async fn count_play(&self, game_type: GameType) {
+        if let Err(err) = self.repository.record_play(game_type, Utc::now()).await {
+            warn!("Failed to count a play for game type {:?}: {}", game_type, err);
+        }
+    }
+
+    /// The player's results that count against the game's limit at `now`.
+    /// A campaign with its own limit only counts plays since it started, from its own database if it has one.
+    async fn plays_in_window(&self, key: &str, game_type: GameType, policy: &LimitPolicy, now: DateTime<Utc>, campaign: Option<&Campaign>) -> Result<Vec<SpinResult>, Error> {
+        let (mut from, to) = policy.window(now);
+        if let Some(campaign) = campaign.filter(|campaign| campaign.limit.is_some()) {
+            from = Some(from.map_or(campaign.starts_at, |from| from.max(campaign.starts_at)));
+        }
+        let filter = EntryFilter {
+            key: Some(key.to_string()),
+            from,
+            to,
+            ..Default::default()
+        };
+
+        match campaign.and_then(|campaign| campaign.database_id.as_deref()) {
+            Some(database_id) => self.repository.find_entries_in(database_id, game_type, &filter).await,
+            None => self.repository.find_entries(game_type, &filter).await,
+        }
+    }
+
+    /// When the next play frees up: the end of the calendar period, or for a
+    /// rolling window when the oldest counted play drops out of it.
+    fn resets_at(policy: &LimitPolicy, plays: &[SpinResult], now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match policy.period {
+            LimitPeriod::Day | LimitPeriod::Week => policy.window(now).1,
+            LimitPeriod::Rolling => plays
+                .iter()
+                .filter_map(SpinResult::played_at)
+                .min()
+                .map(|oldest| oldest + Duration::seconds(policy.window_secs.unwrap_or(0) as i64)),
+            LimitPeriod::Lifetime => None,
+        }
+    }
+
+    /// The limit a play is held to: the campaign's own, else the game's.
+    fn limit_policy(settings: &GameSettings, game_type: GameType, campaign: Option<&Campaign>) -> LimitPolicy {
+        campaign
+            .and_then(|campaign| campaign.limit.clone())
+            .unwrap_or_else(|| settings.limit_policy(game_type))
+    }
+
+    fn parse_page_id(page_id: &str) -> Result<String, Error> {
+        normalize_page_id(page_id).ok_or_else(|| Error::InvalidPageId(page_id.to_string()))
+    }
+
+    async fn has_reached_limit(&self, key: &str, game_type: GameType, campaign: Option<&Campaign>) -> Result<bool, Error> {
+        let settings = self.settings();
+        if settings.is_exempt(key) {
+            debug!("Key {} is exempt from play limits", key);
+            return Ok(false);
+        }
+
+        let policy = Self::limit_policy(&settings, game_type, campaign);
+        debug!("Checking {:?} limit of {} for key: {} with game type: {:?}", policy.period, policy.max, key, game_type);
+
+        let count = self.plays_in_window(key, game_type, &policy, Utc::now(), campaign).await?.len();
+        debug!("Found {} counted plays for key: {} with game type: {:?}", count, key, game_type);
+        Ok(count >= policy.max as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::fake::FakeNotion;
+    use std::collections::HashSet;
+
+    fn play(key: &str, at: DateTime<Utc>) -> SpinResult {
+        SpinResult {
+            key: key.to_string(),
+            datetime: at.to_rfc3339(),
+            number: 123,
+            ..Default::default()
+        }
+    }
+
+    fn limit(period: LimitPeriod, max: u32, window_secs: Option<u64>) -> LimitPolicy {
+        LimitPolicy { period, max, window_secs }
     }
-} 
\ No newline at end of file
+
+    fn service(notion: &FakeNotion, policy: LimitPolicy) -> NotionService<FakeNotion> {
+        let settings = GameSettings {
+            limits: HashMap::from([(GameType::Spin, policy)]),
+            exempt_keys: HashSet::from(["qa".to_string()]),
+            ..Default::default()
+        };
+        NotionService::new(notion.clone(), settings)
+    }
+
+    fn campaign(database_id: Option<&str>, starts_at: DateTime<Utc>, policy: Option<LimitPolicy>) -> Campaign {
+        Campaign {
+            id: "launch".to_string(),
+            name: "Launch".to_string(),
+            game: GameType::Spin,
+            starts_at,
+            ends_at: Utc::now() + Duration::days(1),
+            database_id: database_id.map(str::to_string),
+            limit: policy,
+            prizes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn plays_are_refused_once_the_limit_is_reached() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Day, 2, None));
+        notion.insert(GameType::Spin, play("p1", Utc::now() - Duration::days(1)));
+
+        for _ in 0..2 {
+            service.create_spin_result(play("p1", Utc::now()), GameType::Spin).await.unwrap();
+        }
+        let refused = service.create_spin_result(play("p1", Utc::now()), GameType::Spin).await;
+        assert!(matches!(refused, Err(Error::SpinLimitReached)), "{:?}", refused);
+
+        // Other players and other games have their own count
+        service.create_spin_result(play("p2", Utc::now()), GameType::Spin).await.unwrap();
+        service.create_spin_result(play("p1", Utc::now()), GameType::Wheel).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rolling_limits_count_only_the_window() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Rolling, 1, Some(3600)));
+        notion.insert(GameType::Spin, play("p1", Utc::now() - Duration::minutes(61)));
+
+        service.create_spin_result(play("p1", Utc::now()), GameType::Spin).await.unwrap();
+        assert!(service.create_spin_result(play("p1", Utc::now()), GameType::Spin).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn exempt_keys_are_never_limited() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Lifetime, 1, None));
+
+        for _ in 0..3 {
+            service.create_spin_result(play("qa", Utc::now()), GameType::Spin).await.unwrap();
+        }
+        let status = service.player_status("qa").await.unwrap();
+        assert!(status.exempt);
+        assert_eq!((status.games[0].used, status.games[0].remaining), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn campaign_limits_count_from_the_campaign_start() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Lifetime, 3, None));
+        let started = Utc::now() - Duration::hours(1);
+        let campaign = campaign(None, started, Some(limit(LimitPeriod::Lifetime, 1, None)));
+        // Played before the campaign, in the same database
+        notion.insert(GameType::Spin, play("p1", started - Duration::minutes(1)));
+
+        let created = service.create_campaign_result(play("p1", Utc::now()), &campaign).await.unwrap();
+        assert_eq!(created.campaign.as_deref(), Some("launch"));
+        let refused = service.create_campaign_result(play("p1", Utc::now()), &campaign).await;
+        assert!(matches!(refused, Err(Error::SpinLimitReached)), "{:?}", refused);
+        // The regular game limit still counts every play in the database
+        service.create_spin_result(play("p1", Utc::now()), GameType::Spin).await.unwrap();
+        assert!(service.create_spin_result(play("p1", Utc::now()), GameType::Spin).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn campaign_limits_clamp_the_window_to_the_start() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Day, 1, None));
+        // A 24 hour campaign window that reaches back past the campaign start
+        let started = Utc::now() - Duration::seconds(1);
+        let campaign = campaign(None, started, Some(limit(LimitPeriod::Rolling, 1, Some(86400))));
+        notion.insert(GameType::Spin, play("p1", Utc::now() - Duration::minutes(5)));
+
+        service.create_campaign_result(play("p1", Utc::now()), &campaign).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn campaigns_with_their_own_database_count_only_it() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Day, 1, None));
+        let campaign = campaign(Some("launch-db"), Utc::now() - Duration::hours(1), None);
+        notion.insert(GameType::Spin, play("p1", Utc::now()));
+
+        // Without its own limit the campaign uses the game's, over its own database
+        service.create_campaign_result(play("p1", Utc::now()), &campaign).await.unwrap();
+        assert!(service.create_campaign_result(play("p1", Utc::now()), &campaign).await.is_err());
+        assert_eq!(notion.find_entries_in("launch-db", GameType::Spin, &EntryFilter::default()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn player_status_reports_remaining_plays_and_reset() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Day, 3, None));
+        notion.insert(GameType::Spin, play("p1", Utc::now()));
+
+        let status = service.player_status("p1").await.unwrap();
+        assert!(!status.exempt);
+        let spin = status.games.iter().find(|game| game.game == GameType::Spin).unwrap();
+        assert_eq!((spin.limit, spin.used, spin.remaining), (3, 1, 2));
+        let midnight = LimitPolicy::default().window(Utc::now()).1.unwrap();
+        assert_eq!(spin.resets_at.as_deref(), Some(midnight.to_rfc3339().as_str()));
+    }
+
+    #[tokio::test]
+    async fn player_status_reports_campaign_plays_as_bonus_balance() {
+        let notion = FakeNotion::default();
+        let started = Utc::now() - Duration::hours(1);
+        let mut settings = (*service(&notion, limit(LimitPeriod::Day, 1, None)).settings()).clone();
+        settings.campaigns = vec![
+            campaign(None, started, Some(limit(LimitPeriod::Lifetime, 3, None))),
+            // Shares the game's limit and database, so it adds no plays
+            Campaign { id: "shared".to_string(), ..campaign(None, started, None) },
+            Campaign { id: "ended".to_string(), ends_at: started, ..campaign(Some("old-db"), started - Duration::days(1), None) },
+        ];
+        let service = NotionService::new(notion.clone(), settings);
+        notion.insert(GameType::Spin, play("p1", started - Duration::minutes(1)));
+        notion.insert(GameType::Spin, play("p1", Utc::now()));
+
+        let status = service.player_status("p1").await.unwrap();
+        let spin = status.games.iter().find(|game| game.game == GameType::Spin).unwrap();
+        assert_eq!((spin.remaining, spin.bonus_balance), (0, 2));
+        let wheel = status.games.iter().find(|game| game.game == GameType::Wheel).unwrap();
+        assert_eq!(wheel.bonus_balance, 0);
+        assert_eq!(service.player_status("qa").await.unwrap().games[0].bonus_balance, 3);
+    }
+
+    #[tokio::test]
+    async fn player_history_is_newest_first_across_offsets() {
+        let notion = FakeNotion::default();
+        let service = service(&notion, limit(LimitPeriod::Lifetime, 10, None));
+        let at = |datetime: &str| SpinResult { datetime: datetime.to_string(), ..play("p1", Utc::now()) };
+        notion.insert(GameType::Spin, at("2024-03-01T10:00:00Z"));
+        notion.insert(GameType::Wheel, at("2024-03-01T16:30:00+07:00"));
+        notion.insert(GameType::Spin, at("2024-03-01T16:00:00+07:00"));
+
+        let history = service.player_history("p1", None, 2).await.unwrap();
+        let datetimes: Vec<_> = history.iter().map(|entry| entry.datetime.as_str()).collect();
+        assert_eq!(datetimes, ["2024-03-01T10:00:00Z", "2024-03-01T16:30:00+07:00"]);
+    }
+
+    #[test]
+    fn resets_at_follows_the_period() {
+        let now = DateTime::parse_from_rfc3339("2024-03-15T12:00:00Z").unwrap().with_timezone(&Utc);
+        let plays = [
+            play("p1", now - Duration::minutes(10)),
+            play("p1", now - Duration::minutes(40)),
+            SpinResult { datetime: "not a date".to_string(), ..play("p1", now) },
+        ];
+        let resets_at = |policy: LimitPolicy| NotionService::<FakeNotion>::resets_at(&policy, &plays, now).map(|at| at.to_rfc3339());
+
+        assert_eq!(resets_at(limit(LimitPeriod::Day, 1, None)).as_deref(), Some("2024-03-16T00:00:00+00:00"));
+        assert_eq!(resets_at(limit(LimitPeriod::Week, 1, None)).as_deref(), Some("2024-03-18T00:00:00+00:00"));
+        // The oldest play in the window drops out first
+        assert_eq!(resets_at(limit(LimitPeriod::Rolling, 1, Some(3600))).as_deref(), Some("2024-03-15T12:20:00+00:00"));
+        assert_eq!(resets_at(limit(LimitPeriod::Lifetime, 1, None)), None);
+        assert_eq!(NotionService::<FakeNotion>::resets_at(&limit(LimitPeriod::Rolling, 1, Some(3600)), &[], now), None);
+    }
+}
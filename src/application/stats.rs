@@ -0,0 +1,207 @@
+use chrono::{DateTime, Datelike, Days, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::domain::models::{GameSettings, GameType, SpinResult, Stats, StatsBucket, StatsPeriod, StatsSummary};
+
+/// What a stats request asks for. Also the cache key, so open-ended ranges are
+/// left as `None` rather than filled in with the current time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatsQuery {
+    pub game: Option<GameType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub bucket: StatsBucket,
+}
+
+/// Computed stats kept for `ttl` so dashboards polling the endpoint reuse them.
+pub struct StatsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<StatsQuery, (Instant, Stats)>>,
+}
+
+impl StatsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, query: &StatsQuery) -> Option<Stats> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(query)
+            .filter(|(computed_at, _)| computed_at.elapsed() < self.ttl)
+            .map(|(_, stats)| stats.clone())
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn insert(&self, query: StatsQuery, stats: Stats) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (computed_at, _)| computed_at.elapsed() < self.ttl);
+        entries.insert(query, (Instant::now(), stats));
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    plays: u64,
+    results: usize,
+    wins: usize,
+    fulfilled: usize,
+    keys: HashSet<String>,
+    prizes: BTreeMap<String, usize>,
+}
+
+impl Tally {
+    fn add(&mut self, label: &str, spin_result: &SpinResult) {
+        self.results += 1;
+        if spin_result.is_win {
+            self.wins += 1;
+            if spin_result.checked {
+                self.fulfilled += 1;
+            }
+        }
+        self.keys.insert(spin_result.key.clone());
+        *self.prizes.entry(label.to_string()).or_default() += 1;
+    }
+
+    fn summary(self) -> StatsSummary {
+        let fulfilment_ratio = if self.wins == 0 { 0.0 } else { self.fulfilled as f64 / self.wins as f64 };
+        StatsSummary {
+            plays: self.plays,
+            results: self.results,
+            wins: self.wins,
+            unique_keys: self.keys.len(),
+            prizes: self.prizes,
+            fulfilled: self.fulfilled,
+            fulfilment_ratio,
+        }
+    }
+}
+
+/// Start of the UTC day, ISO week (Monday) or month that `datetime` falls in.
+fn bucket_start(datetime: DateTime<Utc>, bucket: StatsBucket) -> DateTime<Utc> {
+    let date = datetime.date_naive();
+    let start = match bucket {
+        StatsBucket::Day => date,
+        StatsBucket::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+        StatsBucket::Month => date.with_day(1).unwrap(),
+    };
+    start.and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
+
+/// Totals and per-period counts for results and hourly play counts already
+/// narrowed to the query's range. Results with an unreadable datetime count
+/// towards the totals only.
+pub fn summarize(query: &StatsQuery, results: &[(GameType, SpinResult)], plays: &[(DateTime<Utc>, u64)], settings: &GameSettings) -> Stats {
+    let mut totals = Tally::default();
+    let mut periods: BTreeMap<DateTime<Utc>, Tally> = BTreeMap::new();
+
+    for (hour, count) in plays {
+        totals.plays += count;
+        periods.entry(bucket_start(*hour, query.bucket)).or_default().plays += count;
+    }
+
+    for (game_type, spin_result) in results {
+        let label = settings.prize_label(*game_type, spin_result);
+        totals.add(&label, spin_result);
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(&spin_result.datetime) {
+            let start = bucket_start(datetime.with_timezone(&Utc), query.bucket);
+            periods.entry(start).or_default().add(&label, spin_result);
+        }
+    }
+
+    Stats {
+        game: query.game,
+        bucket: query.bucket,
+        from: query.from.map(|from| from.to_rfc3339()),
+        to: query.to.map(|to| to.to_rfc3339()),
+        totals: totals.summary(),
+        buckets: periods
+            .into_iter()
+            .map(|(start, tally)| StatsPeriod { start: start.to_rfc3339(), summary: tally.summary() })
+            .collect(),
+        generated_at: Utc::now().to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(datetime: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(datetime).unwrap().with_timezone(&Utc)
+    }
+
+    fn query(bucket: StatsBucket) -> StatsQuery {
+        StatsQuery { game: None, from: None, to: None, bucket }
+    }
+
+    fn result(key: &str, datetime: &str, is_win: bool, checked: bool) -> (GameType, SpinResult) {
+        let spin_result = SpinResult {
+            key: key.to_string(),
+            datetime: datetime.to_string(),
+            number: 555,
+            is_win,
+            checked,
+            ..Default::default()
+        };
+        (GameType::Spin, spin_result)
+    }
+
+    #[test]
+    fn buckets_start_at_the_utc_day_monday_or_first_of_the_month() {
+        assert_eq!(bucket_start(at("2024-03-17T23:59:59Z"), StatsBucket::Day), at("2024-03-17T00:00:00Z"));
+        // 2024-03-17 is a Sunday, still in the week starting Monday the 11th
+        assert_eq!(bucket_start(at("2024-03-17T23:59:59Z"), StatsBucket::Week), at("2024-03-11T00:00:00Z"));
+        assert_eq!(bucket_start(at("2024-03-18T00:00:00Z"), StatsBucket::Week), at("2024-03-18T00:00:00Z"));
+        // A week that starts in the previous year
+        assert_eq!(bucket_start(at("2025-01-01T12:00:00Z"), StatsBucket::Week), at("2024-12-30T00:00:00Z"));
+        assert_eq!(bucket_start(at("2024-02-29T23:00:00Z"), StatsBucket::Month), at("2024-02-01T00:00:00Z"));
+        assert_eq!(bucket_start(at("2024-03-01T00:00:00Z"), StatsBucket::Month), at("2024-03-01T00:00:00Z"));
+        // Midnight in Bangkok is still the previous day in UTC
+        assert_eq!(bucket_start(at("2024-04-01T00:30:00+07:00"), StatsBucket::Month), at("2024-03-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn summarize_counts_plays_results_and_fulfilment_per_bucket() {
+        let results = [
+            result("p1", "2024-03-17T10:00:00Z", true, true),
+            result("p2", "2024-03-18T10:00:00Z", true, false),
+            result("p1", "2024-03-18T11:00:00+07:00", false, false),
+        ];
+        let plays = [(at("2024-03-17T10:00:00Z"), 4), (at("2024-03-18T03:00:00Z"), 2), (at("2024-03-25T09:00:00Z"), 1)];
+        let stats = summarize(&query(StatsBucket::Week), &results, &plays, &GameSettings::default());
+
+        assert_eq!((stats.totals.plays, stats.totals.results, stats.totals.wins, stats.totals.unique_keys), (7, 3, 2, 2));
+        assert_eq!(stats.totals.prizes, BTreeMap::from([("555".to_string(), 3)]));
+        assert_eq!((stats.totals.fulfilled, stats.totals.fulfilment_ratio), (1, 0.5));
+
+        let buckets: Vec<_> = stats.buckets.iter().map(|period| (period.start.as_str(), period.summary.plays, period.summary.results)).collect();
+        // The last week has plays but no stored results
+        assert_eq!(buckets, [("2024-03-11T00:00:00+00:00", 4, 1), ("2024-03-18T00:00:00+00:00", 2, 2), ("2024-03-25T00:00:00+00:00", 1, 0)]);
+        assert_eq!(stats.buckets[1].summary.fulfilment_ratio, 0.0);
+    }
+
+    #[test]
+    fn fulfilment_ratio_is_zero_without_wins() {
+        let stats = summarize(&query(StatsBucket::Day), &[result("p1", "2024-03-17T10:00:00Z", false, true)], &[], &GameSettings::default());
+        assert_eq!((stats.totals.wins, stats.totals.fulfilled, stats.totals.fulfilment_ratio), (0, 0, 0.0));
+        assert_eq!(summarize(&query(StatsBucket::Day), &[], &[], &GameSettings::default()).totals.fulfilment_ratio, 0.0);
+    }
+
+    #[test]
+    fn unreadable_datetimes_count_towards_the_totals_only() {
+        let results = [result("p1", "2024-03-17", true, false), result("p2", "2024-03-17T10:00:00Z", true, false)];
+        let stats = summarize(&query(StatsBucket::Day), &results, &[], &GameSettings::default());
+
+        assert_eq!((stats.totals.results, stats.totals.unique_keys), (2, 2));
+        assert_eq!(stats.buckets.len(), 1);
+        assert_eq!((stats.buckets[0].summary.results, stats.buckets[0].summary.unique_keys), (1, 1));
+    }
+}
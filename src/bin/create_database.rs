@@ -86,6 +86,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 },
                 "checked": {
                     "checkbox": {}
+                },
+                "fulfilled_by": {
+                    "rich_text": {}
+                },
+                "fulfilled_at": {
+                    "date": {}
+                },
+                "campaign": {
+                    "rich_text": {}
                 }
             }
         }))
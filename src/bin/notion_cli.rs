@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use dotenv::dotenv;
+use notion_crud::config::Config;
+use notion_crud::domain::models::{GameType, SpinResult};
+use notion_crud::domain::repository::NotionRepository;
+use notion_crud::infrastructure::notion::NotionClient;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use validator::Validate;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Load results from a CSV or JSONL file into a game's Notion database
+    Import(ImportArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ImportArgs {
+    /// File to read, with a header row for CSV or one JSON object per line for JSONL
+    file: PathBuf,
+
+    /// Game whose database receives the results
+    #[arg(short, long, default_value = "spin")]
+    game: GameType,
+
+    /// File format, guessed from the file extension when omitted
+    #[arg(short, long)]
+    format: Option<Format>,
+
+    /// Validate and check for duplicates without creating any pages
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Skip data rows before this one (1-based, header not counted)
+    #[arg(long, default_value_t = 1)]
+    resume_from_row: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// A data row from the input file, numbered from 1, and what it parsed into.
+type Row = (usize, Result<SpinResult, String>);
+
+#[derive(Default)]
+struct Summary {
+    created: usize,
+    duplicates: usize,
+    invalid: usize,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+
+    let args = Args::parse();
+    let result = match args.command {
+        Command::Import(import_args) => import(import_args).await,
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn import(args: ImportArgs) -> Result<(), Box<dyn Error>> {
+    let format = args.format
+        .or_else(|| Format::from_path(&args.file))
+        .ok_or("Cannot tell the file format from its extension, pass --format")?;
+    let rows = read_rows(&args.file, format)?;
+    println!("Read {} rows from {}", rows.len(), args.file.display());
+
+    let client = notion_client(args.game)?;
+
+    // Results already in Notion, so re-running an import does not create them twice
+    let mut seen: HashSet<(String, DateTime<Utc>)> = client
+        .get_entries(args.game)
+        .await?
+        .iter()
+        .filter_map(dedup_key)
+        .collect();
+    println!("Found {} existing results in the {} database", seen.len(), args.game.as_str());
+
+    if args.dry_run {
+        println!("Dry run, no pages will be created");
+    }
+
+    let mut summary = Summary::default();
+    for (row, parsed) in rows.into_iter().filter(|(row, _)| *row >= args.resume_from_row) {
+        let spin_result = match parsed.and_then(validate) {
+            Ok(spin_result) => spin_result,
+            Err(err) => {
+                println!("Row {}: invalid, {}", row, err);
+                summary.invalid += 1;
+                continue;
+            }
+        };
+
+        // Validation guarantees a key and a parseable datetime
+        let key = dedup_key(&spin_result).expect("validated row");
+        if !seen.insert(key) {
+            println!("Row {}: skipped duplicate {} at {}", row, spin_result.key, spin_result.datetime);
+            summary.duplicates += 1;
+            continue;
+        }
+
+        if !args.dry_run {
+            if let Err(err) = client.create_entry(spin_result, args.game).await {
+                print_summary(&summary, args.dry_run);
+                return Err(format!("Row {}: {}. Re-run with --resume-from-row {} to continue", row, err, row).into());
+            }
+            println!("Row {}: created", row);
+        }
+        summary.created += 1;
+    }
+
+    print_summary(&summary, args.dry_run);
+    Ok(())
+}
+
+fn notion_client(game_type: GameType) -> Result<NotionClient, Box<dyn Error>> {
+    let config = Config::load()?;
+    let database_ids = HashMap::from([(game_type, config.database_ids()[&game_type].clone())]);
+    Ok(NotionClient::new(database_ids, config.notion.api_token.clone())
+        .with_requests_per_second(config.notion.requests_per_second)
+        .with_timeout(config.notion_timeout()))
+}
+
+fn read_rows(path: &Path, format: Format) -> Result<Vec<Row>, Box<dyn Error>> {
+    let file = File::open(path)?;
+
+    let rows = match format {
+        Format::Csv => csv::Reader::from_reader(file)
+            .deserialize::<SpinResult>()
+            .enumerate()
+            .map(|(index, record)| (index + 1, record.map_err(|err| err.to_string())))
+            .collect(),
+        Format::Jsonl => {
+            let mut rows = Vec::new();
+            for (index, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                rows.push((index + 1, serde_json::from_str::<SpinResult>(&line).map_err(|err| err.to_string())));
+            }
+            rows
+        }
+    };
+
+    Ok(rows)
+}
+
+/// Checks the fields Notion cannot check for us and drops ones it assigns itself.
+fn validate(mut spin_result: SpinResult) -> Result<SpinResult, String> {
+    spin_result.validate().map_err(|err| err.to_string())?;
+
+    spin_result.page_id = None;
+    spin_result.last_edited_time = None;
+    Ok(spin_result)
+}
+
+/// Notion returns datetimes in its own format, so duplicates are compared as instants.
+fn dedup_key(spin_result: &SpinResult) -> Option<(String, DateTime<Utc>)> {
+    let datetime = DateTime::parse_from_rfc3339(&spin_result.datetime).ok()?;
+    Some((spin_result.key.clone(), datetime.with_timezone(&Utc)))
+}
+
+fn print_summary(summary: &Summary, dry_run: bool) {
+    let created = if dry_run { "would be created" } else { "created" };
+    println!(
+        "{} {}, {} duplicates skipped, {} invalid",
+        summary.created, created, summary.duplicates, summary.invalid
+    );
+}
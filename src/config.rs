@@ -0,0 +1,777 @@
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use http::HeaderValue;
+use crate::domain::models::{default_wheel_prizes, Campaign, GameSettings, GameType, LimitPeriod, LimitPolicy, WheelPrize};
+
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Service configuration, read from a TOML file (`CONFIG_FILE`, or `config.toml`
+/// when present) with environment variables taking precedence.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub notion: NotionConfig,
+    pub games: HashMap<GameType, GameConfig>,
+    pub limits: LimitsConfig,
+    pub campaigns: Vec<Campaign>,
+    pub cors: CorsConfig,
+    pub mirror: MirrorConfig,
+    pub stats: StatsConfig,
+    pub health: HealthConfig,
+    pub idempotency: IdempotencyConfig,
+    pub reload: ReloadConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+    pub request_timeout_secs: u64,
+    /// Timeout for bulk operations and exports, which page through Notion at its rate limit.
+    pub long_request_timeout_secs: u64,
+    /// How long shutdown waits for running requests and Notion writes.
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:3000".to_string(),
+            request_timeout_secs: 30,
+            long_request_timeout_secs: 900,
+            drain_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotionConfig {
+    pub api_token: String,
+    pub requests_per_second: u32,
+    pub timeout_secs: u64,
+}
+
+// Written by hand so the API token never ends up in a log
+impl fmt::Debug for NotionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotionConfig")
+            .field("api_token", &"[redacted]")
+            .field("requests_per_second", &self.requests_per_second)
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
+    }
+}
+
+impl Default for NotionConfig {
+    fn default() -> Self {
+        Self {
+            api_token: String::new(),
+            requests_per_second: 3,
+            timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub database_id: String,
+    pub limit: LimitPolicy,
+    /// Wheel slices in order; only valid for the wheel.
+    pub prizes: Option<Vec<WheelPrize>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Player keys never held to a play limit, such as QA testers.
+    pub exempt_keys: Vec<String>,
+}
+
+/// Origins allowed to call the API from a browser. Empty allows any origin.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MirrorConfig {
+    pub sqlite_path: Option<String>,
+    pub resync_secs: u64,
+    /// How often a full sync replaces incremental resyncs, dropping pages archived in Notion.
+    pub full_sync_secs: u64,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self {
+            sqlite_path: None,
+            resync_secs: 60,
+            full_sync_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    pub cache_secs: u64,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self { cache_secs: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// How long a `/readyz` answer is reused before Notion is checked again.
+    pub readiness_cache_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { readiness_cache_secs: 10 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    pub ttl_secs: u64,
+    /// Responses kept at most; the oldest are dropped first once it is reached.
+    pub max_entries: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 86400,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// How often the config file is checked for changes; 0 turns the check off.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReloadConfig {
+    pub watch_secs: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self { watch_secs: 5 }
+    }
+}
+
+/// Every problem found while loading the configuration, so they can all be fixed at once.
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub problems: Vec<String>,
+}
+
+impl ConfigReport {
+    pub fn push(&mut self, problem: impl Into<String>) {
+        self.problems.push(problem.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} configuration problem(s):", self.problems.len())?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigReport {}
+
+impl Config {
+    /// Reads the config file, applies environment overrides and validates the result.
+    ///
+    /// A section that fails to parse is reported and replaced by its defaults, and
+    /// the rest is still checked, so one typo does not hide every other problem.
+    pub fn load() -> Result<Self, ConfigReport> {
+        let mut report = ConfigReport::default();
+        let mut config = Self::read_file(&mut report);
+        config.apply_env(&mut report);
+        config.validate(&mut report);
+
+        if report.is_empty() {
+            Ok(config)
+        } else {
+            Err(report)
+        }
+    }
+
+    /// The config file in use: `CONFIG_FILE`, or `config.toml` if it exists.
+    pub fn file_path() -> Option<PathBuf> {
+        match env::var("CONFIG_FILE") {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+        }
+    }
+
+    fn read_file(report: &mut ConfigReport) -> Self {
+        let Some(path) = Self::file_path() else {
+            return Self::default();
+        };
+        let path = path.display().to_string();
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                report.push(format!("{}: {}", path, err));
+                return Self::default();
+            }
+        };
+        Self::parse_file(&contents, &path, report)
+    }
+
+    /// Builds a configuration from the file's contents, reporting every section
+    /// that does not parse instead of stopping at the first.
+    fn parse_file(contents: &str, path: &str, report: &mut ConfigReport) -> Self {
+        let mut table = match contents.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                report.push(format!("{}: {}", path, err));
+                return Self::default();
+            }
+        };
+
+        let mut config = Self::default();
+        read_section(&mut table, "server", &mut config.server, path, report);
+        read_section(&mut table, "notion", &mut config.notion, path, report);
+        read_section(&mut table, "limits", &mut config.limits, path, report);
+        read_section(&mut table, "cors", &mut config.cors, path, report);
+        read_section(&mut table, "mirror", &mut config.mirror, path, report);
+        read_section(&mut table, "stats", &mut config.stats, path, report);
+        read_section(&mut table, "health", &mut config.health, path, report);
+        read_section(&mut table, "idempotency", &mut config.idempotency, path, report);
+        read_section(&mut table, "reload", &mut config.reload, path, report);
+
+        // Games and campaigns are read one by one so a mistake in one keeps the others
+        let mut games = toml::Table::new();
+        read_section(&mut table, "games", &mut games, path, report);
+        for (name, value) in games {
+            match name.parse::<GameType>() {
+                Ok(game_type) => {
+                    let game = config.games.entry(game_type).or_default();
+                    read_value(value, &format!("games.{}", name), game, path, report);
+                }
+                Err(err) => report.push(format!("{}: games.{}: {}", path, name, err)),
+            }
+        }
+        let mut campaigns = Vec::<toml::Value>::new();
+        read_section(&mut table, "campaigns", &mut campaigns, path, report);
+        for (index, value) in campaigns.into_iter().enumerate() {
+            let mut campaign = None;
+            read_value(value, &format!("campaigns[{}]", index), &mut campaign, path, report);
+            config.campaigns.extend(campaign);
+        }
+
+        for name in table.keys() {
+            report.push(format!("{}: unknown section `{}`", path, name));
+        }
+        config
+    }
+
+    fn apply_env(&mut self, report: &mut ConfigReport) {
+        env_override("BIND_ADDRESS", &mut self.server.bind, report);
+        if let Some(port) = env_value::<u16>("PORT", report) {
+            let host = self.server.bind.rsplit_once(':').map_or("0.0.0.0", |(host, _)| host);
+            self.server.bind = format!("{}:{}", host, port);
+        }
+        env_override("REQUEST_TIMEOUT_SECS", &mut self.server.request_timeout_secs, report);
+        env_override("LONG_REQUEST_TIMEOUT_SECS", &mut self.server.long_request_timeout_secs, report);
+        env_override("SHUTDOWN_DRAIN_SECS", &mut self.server.drain_timeout_secs, report);
+
+        env_override("NOTION_API_TOKEN", &mut self.notion.api_token, report);
+        env_override("NOTION_REQUESTS_PER_SECOND", &mut self.notion.requests_per_second, report);
+        env_override("NOTION_TIMEOUT_SECS", &mut self.notion.timeout_secs, report);
+
+        // NOTION_DATABASE_ID and DAILY_SPIN_LIMIT apply to every game, the
+        // per-game variables win over them
+        let shared_database_id = env_value::<String>("NOTION_DATABASE_ID", report);
+        let shared_limit = env_value::<u32>("DAILY_SPIN_LIMIT", report);
+        for game_type in GameType::ALL {
+            let game = self.games.entry(game_type).or_default();
+            if let Some(database_id) = &shared_database_id {
+                game.database_id = database_id.clone();
+            }
+            if let Some(max) = shared_limit {
+                game.limit.max = max;
+            }
+
+            let suffix = game_type.as_str().to_uppercase();
+            env_override(&format!("NOTION_DATABASE_ID_{}", suffix), &mut game.database_id, report);
+            env_override(&format!("LIMIT_{}", suffix), &mut game.limit.max, report);
+            env_override(&format!("LIMIT_PERIOD_{}", suffix), &mut game.limit.period, report);
+            if let Some(window_secs) = env_value(&format!("LIMIT_WINDOW_SECS_{}", suffix), report) {
+                game.limit.window_secs = Some(window_secs);
+            }
+        }
+
+        if let Ok(keys) = env::var("LIMIT_EXEMPT_KEYS") {
+            self.limits.exempt_keys = split_list(&keys);
+        }
+
+        if let Ok(origins) = env::var("ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = split_list(&origins);
+        }
+
+        if let Ok(path) = env::var("MIRROR_SQLITE_PATH") {
+            self.mirror.sqlite_path = Some(path);
+        }
+        env_override("MIRROR_RESYNC_SECS", &mut self.mirror.resync_secs, report);
+        env_override("MIRROR_FULL_SYNC_SECS", &mut self.mirror.full_sync_secs, report);
+        env_override("STATS_CACHE_SECS", &mut self.stats.cache_secs, report);
+        env_override("READINESS_CACHE_SECS", &mut self.health.readiness_cache_secs, report);
+        env_override("IDEMPOTENCY_TTL_SECS", &mut self.idempotency.ttl_secs, report);
+        env_override("IDEMPOTENCY_MAX_ENTRIES", &mut self.idempotency.max_entries, report);
+        env_override("CONFIG_WATCH_SECS", &mut self.reload.watch_secs, report);
+    }
+
+    fn validate(&self, report: &mut ConfigReport) {
+        if let Err(err) = self.server.bind.parse::<SocketAddr>() {
+            report.push(format!("server.bind {:?} is not an address: {}", self.server.bind, err));
+        }
+        if self.server.request_timeout_secs == 0 {
+            report.push("server.request_timeout_secs must be greater than 0");
+        }
+        if self.server.long_request_timeout_secs < self.server.request_timeout_secs {
+            report.push("server.long_request_timeout_secs must be at least server.request_timeout_secs");
+        }
+
+        if self.notion.api_token.trim().is_empty() {
+            report.push("notion.api_token is not set (or NOTION_API_TOKEN)");
+        }
+        if self.notion.requests_per_second == 0 {
+            report.push("notion.requests_per_second must be greater than 0");
+        }
+        if self.notion.timeout_secs == 0 {
+            report.push("notion.timeout_secs must be greater than 0");
+        }
+
+        for game_type in GameType::ALL {
+            let game = game_type.as_str();
+            let Some(game_config) = self.games.get(&game_type).filter(|config| !config.database_id.trim().is_empty()) else {
+                report.push(format!(
+                    "games.{}.database_id is not set (or NOTION_DATABASE_ID_{} / NOTION_DATABASE_ID)",
+                    game,
+                    game.to_uppercase()
+                ));
+                continue;
+            };
+
+            let section = format!("games.{}", game);
+            check_limit(&section, &game_config.limit, report);
+            if let Some(prizes) = &game_config.prizes {
+                check_prizes(&section, game_type, prizes, report);
+            }
+        }
+
+        let mut campaign_ids = HashSet::new();
+        for (index, campaign) in self.campaigns.iter().enumerate() {
+            let section = format!("campaigns[{}]", index);
+            if campaign.id.trim().is_empty() {
+                report.push(format!("{}.id is empty", section));
+            } else if !campaign_ids.insert(campaign.id.as_str()) {
+                report.push(format!("{}.id {:?} is used by another campaign", section, campaign.id));
+            }
+            if campaign.ends_at <= campaign.starts_at {
+                report.push(format!("{}.ends_at must be after starts_at", section));
+            }
+            if campaign.database_id.as_deref().is_some_and(|database_id| database_id.trim().is_empty()) {
+                report.push(format!("{}.database_id is empty", section));
+            }
+            if let Some(limit) = &campaign.limit {
+                check_limit(&section, limit, report);
+            }
+            if let Some(prizes) = &campaign.prizes {
+                check_prizes(&section, campaign.game, prizes, report);
+            }
+        }
+
+        for origin in &self.cors.allowed_origins {
+            let is_url = origin.starts_with("http://") || origin.starts_with("https://");
+            if !is_url || origin.parse::<HeaderValue>().is_err() {
+                report.push(format!("cors.allowed_origins entry {:?} is not an http(s) origin", origin));
+            }
+        }
+
+        if self.idempotency.max_entries == 0 {
+            report.push("idempotency.max_entries must be greater than 0");
+        }
+
+        if self.mirror.resync_secs == 0 {
+            report.push("mirror.resync_secs must be greater than 0");
+        }
+        if self.mirror.full_sync_secs < self.mirror.resync_secs {
+            report.push("mirror.full_sync_secs must be at least mirror.resync_secs");
+        }
+    }
+
+    pub fn database_ids(&self) -> HashMap<GameType, String> {
+        self.games
+            .iter()
+            .map(|(game_type, game)| (*game_type, game.database_id.clone()))
+            .collect()
+    }
+
+    /// The part of the configuration that can be swapped in while running.
+    pub fn game_settings(&self) -> GameSettings {
+        GameSettings {
+            limits: self.games
+                .iter()
+                .map(|(game_type, game)| (*game_type, game.limit.clone()))
+                .collect(),
+            exempt_keys: self.limits.exempt_keys.iter().cloned().collect(),
+            wheel_prizes: self.games
+                .get(&GameType::Wheel)
+                .and_then(|game| game.prizes.clone())
+                .unwrap_or_else(default_wheel_prizes),
+            campaigns: self.campaigns.clone(),
+        }
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        self.server.bind.parse().expect("validated bind address")
+    }
+
+    pub fn allowed_origins(&self) -> Vec<HeaderValue> {
+        self.cors.allowed_origins
+            .iter()
+            .filter_map(|origin| origin.parse().ok())
+            .collect()
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.request_timeout_secs)
+    }
+
+    pub fn long_request_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.long_request_timeout_secs)
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.drain_timeout_secs)
+    }
+
+    pub fn notion_timeout(&self) -> Duration {
+        Duration::from_secs(self.notion.timeout_secs)
+    }
+}
+
+fn check_limit(section: &str, limit: &LimitPolicy, report: &mut ConfigReport) {
+    match (limit.period, limit.window_secs) {
+        (LimitPeriod::Rolling, None | Some(0)) => {
+            report.push(format!("{}.limit.window_secs must be greater than 0 for a rolling limit", section));
+        }
+        (LimitPeriod::Day | LimitPeriod::Week | LimitPeriod::Lifetime, Some(_)) => {
+            report.push(format!("{}.limit.window_secs only applies to a rolling limit", section));
+        }
+        _ => {}
+    }
+}
+
+fn check_prizes(section: &str, game_type: GameType, prizes: &[WheelPrize], report: &mut ConfigReport) {
+    if game_type != GameType::Wheel {
+        report.push(format!("{}.prizes only applies to the wheel", section));
+    } else if prizes.iter().map(|prize| prize.weight).sum::<u32>() == 0 {
+        report.push(format!("{}.prizes needs at least one slice with a weight above 0", section));
+    }
+    if prizes.iter().any(|prize| prize.label.trim().is_empty()) {
+        report.push(format!("{}.prizes has a slice without a label", section));
+    }
+}
+
+/// Replaces `target` with the section `name` from the file, if it is there and valid.
+fn read_section<T: DeserializeOwned>(table: &mut toml::Table, name: &str, target: &mut T, path: &str, report: &mut ConfigReport) {
+    if let Some(value) = table.remove(name) {
+        read_value(value, name, target, path, report);
+    }
+}
+
+fn read_value<T: DeserializeOwned>(value: toml::Value, name: &str, target: &mut T, path: &str, report: &mut ConfigReport) {
+    match value.try_into() {
+        Ok(parsed) => *target = parsed,
+        Err(err) => report.push(format!("{}: {}: {}", path, name, err.message())),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn env_value<T>(name: &str, report: &mut ConfigReport) -> Option<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(err) => {
+            report.push(format!("{}={:?} is invalid: {}", name, value, err));
+            None
+        }
+    }
+}
+
+fn env_override<T>(name: &str, target: &mut T, report: &mut ConfigReport)
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(value) = env_value(name, report) {
+        *target = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Tests that set environment variables hold this, as the environment is shared by every test thread.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const VALID: &str = r#"
+        [notion]
+        api_token = "secret_token"
+
+        [games.spin]
+        database_id = "spin-db"
+
+        [games.wheel]
+        database_id = "wheel-db"
+        limit = { period = "rolling", max = 3, window_secs = 3600 }
+    "#;
+
+    fn parse(contents: &str) -> (Config, Vec<String>) {
+        let mut report = ConfigReport::default();
+        let config = Config::parse_file(contents, "config.toml", &mut report);
+        config.validate(&mut report);
+        (config, report.problems)
+    }
+
+    fn assert_reported(problems: &[String], expected: &str) {
+        assert!(
+            problems.iter().any(|problem| problem.contains(expected)),
+            "expected a problem containing {:?}, got {:#?}",
+            expected,
+            problems,
+        );
+    }
+
+    #[test]
+    fn valid_file_has_no_problems() {
+        let (config, problems) = parse(VALID);
+        assert!(problems.is_empty(), "{:#?}", problems);
+        assert_eq!(config.database_ids()[&GameType::Spin], "spin-db");
+        assert_eq!(config.game_settings().limit_policy(GameType::Wheel).period, LimitPeriod::Rolling);
+        assert_eq!(config.game_settings().limit_policy(GameType::Spin), LimitPolicy::default());
+    }
+
+    #[test]
+    fn every_broken_section_is_reported_and_the_rest_still_validated() {
+        let (config, problems) = parse(
+            r#"
+            [server]
+            request_timeout_secs = "thirty"
+
+            [notion]
+            api_token = "secret_token"
+            requests_per_second = 0
+
+            [games.spin]
+            database_id = "spin-db"
+
+            [games.dice]
+            database_id = "dice-db"
+
+            [caching]
+            enabled = true
+            "#,
+        );
+
+        assert_reported(&problems, "config.toml: server");
+        assert_reported(&problems, "games.dice");
+        assert_reported(&problems, "unknown section `caching`");
+        // Sections after the broken one are still read and checked
+        assert_reported(&problems, "notion.requests_per_second must be greater than 0");
+        assert_reported(&problems, "games.wheel.database_id is not set");
+        assert_eq!(config.server.request_timeout_secs, ServerConfig::default().request_timeout_secs);
+        assert_eq!(config.notion.api_token, "secret_token");
+    }
+
+    #[test]
+    fn one_broken_campaign_keeps_the_others() {
+        let (config, problems) = parse(&format!(
+            r#"{}
+            [[campaigns]]
+            id = "launch"
+            name = "Launch week"
+            game = "wheel"
+            starts_at = "2024-03-01T00:00:00Z"
+            ends_at = "2024-03-08T00:00:00Z"
+
+            [[campaigns]]
+            id = "broken"
+            name = "Broken"
+            game = "wheel"
+            starts_at = "not a date"
+            ends_at = "2024-03-08T00:00:00Z"
+            "#,
+            VALID,
+        ));
+        assert_reported(&problems, "campaigns[1]");
+        assert_eq!(config.campaigns.iter().map(|campaign| campaign.id.as_str()).collect::<Vec<_>>(), ["launch"]);
+    }
+
+    #[test]
+    fn unparseable_file_is_reported_with_every_missing_setting() {
+        let (_, problems) = parse("[notion\napi_token = ");
+        assert_reported(&problems, "config.toml: ");
+        assert_reported(&problems, "notion.api_token is not set");
+        assert_reported(&problems, "games.spin.database_id is not set");
+    }
+
+    #[test]
+    fn invalid_values_are_reported() {
+        let (_, problems) = parse(&format!(
+            r#"{}
+            [server]
+            bind = "localhost"
+            request_timeout_secs = 60
+            long_request_timeout_secs = 30
+
+            [cors]
+            allowed_origins = ["https://example.com", "example.com"]
+
+            [mirror]
+            resync_secs = 120
+            full_sync_secs = 60
+
+            [[campaigns]]
+            id = "launch"
+            name = "Launch"
+            game = "spin"
+            starts_at = "2024-03-08T00:00:00Z"
+            ends_at = "2024-03-01T00:00:00Z"
+            limit = {{ period = "day", max = 1, window_secs = 60 }}
+            prizes = [{{ label = "", weight = 0 }}]
+
+            [[campaigns]]
+            id = "launch"
+            name = "Launch again"
+            game = "wheel"
+            starts_at = "2024-03-01T00:00:00Z"
+            ends_at = "2024-03-08T00:00:00Z"
+            limit = {{ period = "rolling", max = 1 }}
+            prizes = [{{ label = "Nothing", weight = 0 }}]
+            "#,
+            VALID,
+        ));
+
+        for expected in [
+            "server.bind \"localhost\" is not an address",
+            "server.long_request_timeout_secs must be at least server.request_timeout_secs",
+            "cors.allowed_origins entry \"example.com\"",
+            "mirror.full_sync_secs must be at least mirror.resync_secs",
+            "campaigns[0].ends_at must be after starts_at",
+            "campaigns[0].limit.window_secs only applies to a rolling limit",
+            "campaigns[0].prizes only applies to the wheel",
+            "campaigns[0].prizes has a slice without a label",
+            "campaigns[1].id \"launch\" is used by another campaign",
+            "campaigns[1].limit.window_secs must be greater than 0",
+            "campaigns[1].prizes needs at least one slice with a weight above 0",
+        ] {
+            assert_reported(&problems, expected);
+        }
+        assert_eq!(problems.len(), 11, "{:#?}", problems);
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let _env = ENV.lock().unwrap();
+        let vars = [
+            ("PORT", "8080"),
+            ("NOTION_DATABASE_ID", "shared-db"),
+            ("NOTION_DATABASE_ID_WHEEL", "wheel-env-db"),
+            ("DAILY_SPIN_LIMIT", "5"),
+            ("LIMIT_WHEEL", "2"),
+            ("LIMIT_PERIOD_SPIN", "week"),
+            ("LIMIT_EXEMPT_KEYS", "qa-1, qa-2,,"),
+        ];
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+
+        let mut report = ConfigReport::default();
+        let mut config = Config::parse_file(VALID, "config.toml", &mut report);
+        config.server.bind = "127.0.0.1:3000".to_string();
+        config.apply_env(&mut report);
+        config.validate(&mut report);
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+
+        assert!(report.is_empty(), "{}", report);
+        assert_eq!(config.server.bind, "127.0.0.1:8080");
+        assert_eq!(config.games[&GameType::Spin].database_id, "shared-db");
+        assert_eq!(config.games[&GameType::Wheel].database_id, "wheel-env-db");
+        assert_eq!(config.games[&GameType::Spin].limit, LimitPolicy { period: LimitPeriod::Week, max: 5, window_secs: None });
+        // The file's rolling window is kept when only the maximum is overridden
+        assert_eq!(config.games[&GameType::Wheel].limit, LimitPolicy { period: LimitPeriod::Rolling, max: 2, window_secs: Some(3600) });
+        assert!(config.game_settings().is_exempt("qa-2"));
+        assert_eq!(config.limits.exempt_keys, ["qa-1", "qa-2"]);
+    }
+
+    #[test]
+    fn invalid_environment_values_are_reported() {
+        let _env = ENV.lock().unwrap();
+        let vars = [("REQUEST_TIMEOUT_SECS", "soon"), ("LIMIT_PERIOD_WHEEL", "fortnight")];
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+
+        let mut report = ConfigReport::default();
+        let mut config = Config::parse_file(VALID, "config.toml", &mut report);
+        config.apply_env(&mut report);
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+
+        assert_reported(&report.problems, "REQUEST_TIMEOUT_SECS=\"soon\" is invalid");
+        assert_reported(&report.problems, "LIMIT_PERIOD_WHEEL=\"fortnight\" is invalid");
+        assert_eq!(config.server.request_timeout_secs, ServerConfig::default().request_timeout_secs);
+        assert_eq!(config.games[&GameType::Wheel].limit.period, LimitPeriod::Rolling);
+    }
+}
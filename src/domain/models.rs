@@ -1,48 +1,547 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hash;
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Days, Duration, NaiveDate, Utc};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
 pub enum GameType {
     Spin,
     Wheel,
     // Add more games here in the future
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl GameType {
+    pub const ALL: [GameType; 2] = [GameType::Spin, GameType::Wheel];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameType::Spin => "spin",
+            GameType::Wheel => "wheel",
+        }
+    }
+}
+
+impl FromStr for GameType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GameType::ALL
+            .into_iter()
+            .find(|game_type| game_type.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown game type: {}", s))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, Validate)]
 pub struct SpinResult {
+    #[validate(
+        length(min = 1, max = MAX_KEY_LENGTH, message = "must be 1 to 128 characters"),
+        custom(function = "validate_key"),
+    )]
     pub key: String,
+    #[validate(custom(function = "validate_datetime"))]
     pub datetime: String,
+    #[validate(range(min = 0, max = MAX_RESULT_NUMBER, message = "must be between 0 and 999"))]
     pub number: i32,
     pub is_win: bool,
     pub checked: bool,
     // Optional field to store which game this result is for
     #[serde(skip_serializing_if = "Option::is_none")]
     pub game_type: Option<String>,
+    // Set by the repository for entries that exist in Notion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_edited_time: Option<String>,
+    // Who marked the prize as paid out, and when
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = MAX_KEY_LENGTH, message = "must be at most 128 characters"))]
+    pub fulfilled_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_datetime"))]
+    pub fulfilled_at: Option<String>,
+    // The campaign the play was made in, whose prize table `number` indexes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = MAX_KEY_LENGTH, message = "must be at most 128 characters"))]
+    pub campaign: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl SpinResult {
+    /// When the play happened, as an instant: results store `datetime` with
+    /// whatever offset they were sent with, so the strings do not sort.
+    pub fn played_at(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.datetime).ok().map(|datetime| datetime.with_timezone(&Utc))
+    }
+}
+
+const MAX_KEY_LENGTH: u64 = 128;
+/// Spin results are three digits and wheel results a slice index.
+const MAX_RESULT_NUMBER: i32 = 999;
+/// How far ahead of the server's clock a datetime may be and still count as now.
+const CLOCK_SKEW_SECS: i64 = 300;
+
+/// Player keys are ids, phone numbers or e-mail addresses, never free text.
+fn validate_key(key: &str) -> Result<(), ValidationError> {
+    if key.chars().all(|c| c.is_alphanumeric() || "-_.@+:".contains(c)) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("charset").with_message("may only contain letters, digits and - _ . @ + :".into()))
+    }
+}
+
+/// Notion rejects anything but RFC 3339, and a play cannot have happened yet.
+fn validate_datetime(datetime: &str) -> Result<(), ValidationError> {
+    let parsed = DateTime::parse_from_rfc3339(datetime)
+        .map_err(|_| ValidationError::new("rfc3339").with_message("must be an RFC 3339 datetime".into()))?;
+    if parsed > Utc::now() + Duration::seconds(CLOCK_SKEW_SECS) {
+        return Err(ValidationError::new("future").with_message("must not be in the future".into()));
+    }
+    Ok(())
+}
+
+/// Validates a Notion page id given with or without dashes and returns it in the
+/// canonical lowercase, dashed UUID form.
+pub fn normalize_page_id(page_id: &str) -> Option<String> {
+    let hex: String = page_id.trim().chars().filter(|c| *c != '-').collect();
+    let dashes_valid = match page_id.trim().len() {
+        32 => true,
+        36 => [8, 13, 18, 23].iter().all(|&i| page_id.trim().as_bytes()[i] == b'-'),
+        _ => false,
+    };
+    if !dashes_valid || hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let hex = hex.to_ascii_lowercase();
+    Some(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+/// Fields to change on a stored result. Fields left as `None` keep their current value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, Validate)]
+pub struct SpinResultPatch {
+    #[validate(
+        length(min = 1, max = MAX_KEY_LENGTH, message = "must be 1 to 128 characters"),
+        custom(function = "validate_key"),
+    )]
+    pub key: Option<String>,
+    #[validate(custom(function = "validate_datetime"))]
+    pub datetime: Option<String>,
+    #[validate(range(min = 0, max = MAX_RESULT_NUMBER, message = "must be between 0 and 999"))]
+    pub number: Option<i32>,
+    pub is_win: Option<bool>,
+    pub checked: Option<bool>,
+    #[validate(length(max = MAX_KEY_LENGTH, message = "must be at most 128 characters"))]
+    pub fulfilled_by: Option<String>,
+    #[validate(custom(function = "validate_datetime"))]
+    pub fulfilled_at: Option<String>,
+}
+
+impl SpinResultPatch {
+    pub fn is_empty(&self) -> bool {
+        self.key.is_none()
+            && self.datetime.is_none()
+            && self.number.is_none()
+            && self.is_win.is_none()
+            && self.checked.is_none()
+            && self.fulfilled_by.is_none()
+            && self.fulfilled_at.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitPeriod {
+    /// Calendar day in UTC.
+    #[default]
+    Day,
+    /// Calendar week in UTC, starting Monday.
+    Week,
+    /// The last `window_secs` seconds.
+    Rolling,
+    /// Every play ever recorded.
+    Lifetime,
+}
+
+impl FromStr for LimitPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "day" => Ok(LimitPeriod::Day),
+            "week" => Ok(LimitPeriod::Week),
+            "rolling" => Ok(LimitPeriod::Rolling),
+            "lifetime" => Ok(LimitPeriod::Lifetime),
+            _ => Err(format!("Unknown limit period: {}", s)),
+        }
+    }
+}
+
+/// How many plays a key gets in a game, and over what period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitPolicy {
+    pub period: LimitPeriod,
+    pub max: u32,
+    /// Length of the window for the `rolling` period.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_secs: Option<u64>,
+}
+
+impl Default for LimitPolicy {
+    fn default() -> Self {
+        Self {
+            period: LimitPeriod::Day,
+            max: 1,
+            window_secs: None,
+        }
+    }
+}
+
+impl LimitPolicy {
+    /// The span of plays that count against the limit at `now`. Open ends are `None`.
+    pub fn window(&self, now: DateTime<Utc>) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let today = now.date_naive();
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+        match self.period {
+            LimitPeriod::Day => {
+                let start = midnight(today);
+                (Some(start), Some(start + Duration::days(1)))
+            }
+            LimitPeriod::Week => {
+                let start = midnight(today - Days::new(today.weekday().num_days_from_monday() as u64));
+                (Some(start), Some(start + Duration::weeks(1)))
+            }
+            LimitPeriod::Rolling => {
+                let window = Duration::seconds(self.window_secs.unwrap_or(0) as i64);
+                (Some(now - window), None)
+            }
+            LimitPeriod::Lifetime => (None, None),
+        }
+    }
+}
+
+/// Where a player stands against the play limit of one game.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlayerGameStatus {
+    pub game: GameType,
+    pub period: LimitPeriod,
+    pub limit: u32,
+    pub used: usize,
+    pub remaining: usize,
+    /// Plays left in campaigns running now for this game, on top of `remaining`.
+    pub bonus_balance: usize,
+    /// When a play next frees up; absent for lifetime limits or an unused rolling window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlayerStatus {
+    pub key: String,
+    /// Keys on the allowlist are never limited.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub exempt: bool,
+    pub games: Vec<PlayerGameStatus>,
+}
+
+/// A past outcome as shown to the player, without admin-only fields.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlayerHistoryEntry {
+    pub game: GameType,
+    pub datetime: String,
+    pub number: i32,
+    pub is_win: bool,
+    pub fulfilled: bool,
+}
+
+/// A result archived through the API, kept so it can be restored.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedResult {
+    #[serde(flatten)]
+    pub result: SpinResult,
+    pub archived_at: String,
+}
+
+/// One item of a `POST /spin-results/bulk` request.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Create { result: SpinResult },
+    Update { page_id: String, patch: SpinResultPatch },
+    Archive { page_id: String },
+}
+
+impl BulkOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkOperation::Create { .. } => "create",
+            BulkOperation::Update { .. } => "update",
+            BulkOperation::Archive { .. } => "archive",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkRequest {
+    pub operations: Vec<BulkOperation>,
+}
+
+/// Outcome of one bulk operation, reported at the same `index` as in the request.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkItemResult {
+    pub index: usize,
+    pub op: &'static str,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResponse {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+/// Criteria for selecting stored results. Empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    pub key: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub is_win: Option<bool>,
+    pub checked: Option<bool>,
+}
+
+impl EntryFilter {
+    pub fn matches(&self, spin_result: &SpinResult) -> bool {
+        if let Some(key) = &self.key {
+            if &spin_result.key != key {
+                return false;
+            }
+        }
+        if self.is_win.is_some_and(|is_win| spin_result.is_win != is_win) {
+            return false;
+        }
+        if self.checked.is_some_and(|checked| spin_result.checked != checked) {
+            return false;
+        }
+
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+
+        let Some(datetime) = spin_result.played_at() else {
+            return false;
+        };
+
+        self.from.is_none_or(|from| datetime >= from) && self.to.is_none_or(|to| datetime < to)
+    }
+}
+
+/// One page of query results and the cursor to fetch the next, if there is one.
+#[derive(Debug, Clone, Default)]
+pub struct EntryPage {
+    pub entries: Vec<SpinResult>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SpinRequest {
     pub key: Option<String>,
+    /// Play as part of this campaign instead of the regular game.
+    #[serde(default)]
+    pub campaign: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SpinResponse {
     pub numbers: Vec<String>,
     pub is_win: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct WheelRequest {
     pub key: Option<String>,
+    /// Play as part of this campaign instead of the regular game.
+    #[serde(default)]
+    pub campaign: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct WheelResponse {
     pub prize_index: usize,
     pub prize_name: String,
     pub is_win: bool,
 }
 
+/// One slice of the wheel. A wheel result's `number` is the index of the slice it landed on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WheelPrize {
+    pub label: String,
+    /// Relative chance of landing here; 0 means the slice is never landed on.
+    pub weight: u32,
+    /// Landing here is a win and is recorded in Notion.
+    #[serde(default)]
+    pub is_win: bool,
+}
+
+impl WheelPrize {
+    fn new(label: &str, weight: u32, is_win: bool) -> Self {
+        Self { label: label.to_string(), weight, is_win }
+    }
+}
+
+/// The wheel as shipped with the frontend, used when the config does not set one.
+pub fn default_wheel_prizes() -> Vec<WheelPrize> {
+    vec![
+        WheelPrize::new("รับเครดิต 500", 0, false),
+        WheelPrize::new("หมุนฟรี 1 ครั้ง", 30, false),
+        WheelPrize::new("รับเครดิต 50", 5, true),
+        WheelPrize::new("แย่จัง", 35, false),
+        WheelPrize::new("รับเครดิต 300", 0, false),
+        WheelPrize::new("หมุนฟรี 1 ครั้ง", 30, false),
+        WheelPrize::new("รับเครดิต 100", 5, true),
+        WheelPrize::new("แย่จัง", 35, false),
+    ]
+}
+
+/// A time-boxed promotion on one game, with its own rules while it runs.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Campaign {
+    pub id: String,
+    pub name: String,
+    pub game: GameType,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// Notion database for the campaign's results; the game's database when unset.
+    #[serde(default, skip_serializing)]
+    pub database_id: Option<String>,
+    /// Limit for campaign plays, counted from the campaign start; the game's limit when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<LimitPolicy>,
+    /// Wheel slices for campaign plays; the regular wheel when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prizes: Option<Vec<WheelPrize>>,
+}
+
+impl Campaign {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+}
+
+/// Game rules that can be changed while the server is running.
+#[derive(Debug, Clone)]
+pub struct GameSettings {
+    pub limits: HashMap<GameType, LimitPolicy>,
+    /// Player keys never held to a limit, such as QA testers.
+    pub exempt_keys: HashSet<String>,
+    pub wheel_prizes: Vec<WheelPrize>,
+    pub campaigns: Vec<Campaign>,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            limits: HashMap::new(),
+            exempt_keys: HashSet::new(),
+            wheel_prizes: default_wheel_prizes(),
+            campaigns: Vec::new(),
+        }
+    }
+}
+
+impl GameSettings {
+    pub fn limit_policy(&self, game_type: GameType) -> LimitPolicy {
+        self.limits.get(&game_type).cloned().unwrap_or_default()
+    }
+
+    pub fn is_exempt(&self, key: &str) -> bool {
+        self.exempt_keys.contains(key)
+    }
+
+    pub fn active_campaigns(&self, now: DateTime<Utc>) -> Vec<Campaign> {
+        self.campaigns.iter().filter(|campaign| campaign.is_active(now)).cloned().collect()
+    }
+
+    pub fn campaign(&self, id: &str) -> Option<&Campaign> {
+        self.campaigns.iter().find(|campaign| campaign.id == id)
+    }
+
+    /// How a stored result's `number` reads to a person: the slice label from the
+    /// wheel it was spun on, the campaign's if it has its own, or the spun digits
+    /// for the slot game.
+    pub fn prize_label(&self, game_type: GameType, spin_result: &SpinResult) -> String {
+        let number = spin_result.number;
+        let prizes = match &spin_result.campaign {
+            Some(id) => self.campaign(id).map(|campaign| campaign.prizes.as_ref().unwrap_or(&self.wheel_prizes)),
+            None => Some(&self.wheel_prizes),
+        };
+        match game_type {
+            GameType::Spin => number.to_string(),
+            GameType::Wheel => usize::try_from(number)
+                .ok()
+                .and_then(|index| prizes?.get(index))
+                .map_or_else(|| format!("unknown ({})", number), |prize| prize.label.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+/// Counts over the plays and stored results in a range.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct StatsSummary {
+    /// Every play, won or lost, counted by the hour it started in.
+    pub plays: u64,
+    /// Results stored in Notion: the winning plays, plus any added through the admin API.
+    pub results: usize,
+    pub wins: usize,
+    pub unique_keys: usize,
+    pub prizes: BTreeMap<String, usize>,
+    pub fulfilled: usize,
+    /// Share of wins that have been paid out, 0 when there are no wins.
+    pub fulfilment_ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StatsPeriod {
+    pub start: String,
+    #[serde(flatten)]
+    pub summary: StatsSummary,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Stats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game: Option<GameType>,
+    pub bucket: StatsBucket,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    pub totals: StatsSummary,
+    pub buckets: Vec<StatsPeriod>,
+    pub generated_at: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NotionProperties {
     pub key: NotionTitle,
@@ -50,6 +549,12 @@ pub struct NotionProperties {
     pub number: NotionNumber,
     pub is_win: NotionCheckbox,
     pub checked: NotionCheckbox,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fulfilled_by: Option<NotionRichText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fulfilled_at: Option<NotionDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<NotionRichText>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -81,6 +586,7 @@ pub struct NotionCheckbox {
     pub checkbox: bool,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NotionPhoneNumber {
     pub r#type: String,
@@ -102,4 +608,258 @@ pub struct NotionText {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NotionTextContent {
     pub content: String,
-} 
\ No newline at end of file
+} 
+
+/// Whether something the API relies on could be reached on the last check.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    pub name: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyStatus {
+    pub fn new(name: impl Into<String>, result: Result<(), impl std::fmt::Display>) -> Self {
+        Self {
+            name: name.into(),
+            ok: result.is_ok(),
+            error: result.err().map(|err| err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub dependencies: Vec<DependencyStatus>,
+    pub checked_at: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(datetime: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(datetime).unwrap().with_timezone(&Utc)
+    }
+
+    fn policy(period: LimitPeriod, window_secs: Option<u64>) -> LimitPolicy {
+        LimitPolicy { period, max: 1, window_secs }
+    }
+
+    #[test]
+    fn day_window_is_the_utc_calendar_day() {
+        let day = policy(LimitPeriod::Day, None);
+        assert_eq!(
+            day.window(at("2024-03-15T13:45:00Z")),
+            (Some(at("2024-03-15T00:00:00Z")), Some(at("2024-03-16T00:00:00Z"))),
+        );
+        // The last second before midnight and midnight itself fall on different days
+        assert_eq!(day.window(at("2024-03-15T23:59:59Z")).1, Some(at("2024-03-16T00:00:00Z")));
+        assert_eq!(day.window(at("2024-03-16T00:00:00Z")).0, Some(at("2024-03-16T00:00:00Z")));
+    }
+
+    #[test]
+    fn day_window_ignores_the_players_timezone() {
+        // Already the 16th in UTC+2, still the 15th in UTC
+        let (start, end) = policy(LimitPeriod::Day, None).window(at("2024-03-16T01:30:00+02:00"));
+        assert_eq!(start, Some(at("2024-03-15T00:00:00Z")));
+        assert_eq!(end, Some(at("2024-03-16T00:00:00Z")));
+    }
+
+    #[test]
+    fn week_window_starts_on_monday() {
+        let week = policy(LimitPeriod::Week, None);
+        let expected = (Some(at("2024-03-11T00:00:00Z")), Some(at("2024-03-18T00:00:00Z")));
+        assert_eq!(week.window(at("2024-03-11T00:00:00Z")), expected);
+        assert_eq!(week.window(at("2024-03-14T12:00:00Z")), expected);
+        assert_eq!(week.window(at("2024-03-17T23:59:59Z")), expected);
+        assert_eq!(week.window(at("2024-03-18T00:00:00Z")).0, Some(at("2024-03-18T00:00:00Z")));
+    }
+
+    #[test]
+    fn week_window_spans_month_and_year_ends() {
+        assert_eq!(
+            policy(LimitPeriod::Week, None).window(at("2025-01-01T08:00:00Z")),
+            (Some(at("2024-12-30T00:00:00Z")), Some(at("2025-01-06T00:00:00Z"))),
+        );
+    }
+
+    #[test]
+    fn rolling_window_looks_back_from_now() {
+        let now = at("2024-03-15T00:30:00Z");
+        assert_eq!(
+            policy(LimitPeriod::Rolling, Some(3600)).window(now),
+            (Some(at("2024-03-14T23:30:00Z")), None),
+        );
+        assert_eq!(policy(LimitPeriod::Rolling, None).window(now), (Some(now), None));
+    }
+
+    #[test]
+    fn lifetime_window_is_unbounded() {
+        assert_eq!(policy(LimitPeriod::Lifetime, None).window(at("2024-03-15T12:00:00Z")), (None, None));
+    }
+
+    #[test]
+    fn games_without_a_policy_get_one_play_a_day() {
+        let mut settings = GameSettings::default();
+        settings.limits.insert(GameType::Wheel, policy(LimitPeriod::Week, None));
+
+        assert_eq!(settings.limit_policy(GameType::Spin), LimitPolicy::default());
+        assert_eq!(settings.limit_policy(GameType::Wheel).period, LimitPeriod::Week);
+    }
+
+    #[test]
+    fn exempt_keys_match_exactly() {
+        let settings = GameSettings {
+            exempt_keys: HashSet::from(["qa-tester".to_string()]),
+            ..Default::default()
+        };
+        assert!(settings.is_exempt("qa-tester"));
+        assert!(!settings.is_exempt("QA-tester"));
+        assert!(!settings.is_exempt("qa-tester-2"));
+        assert!(!GameSettings::default().is_exempt("qa-tester"));
+    }
+
+    #[test]
+    fn prize_labels_come_from_the_wheel_the_result_was_spun_on() {
+        let campaign = |id: &str, prizes: Option<Vec<WheelPrize>>| Campaign {
+            id: id.to_string(),
+            name: id.to_string(),
+            game: GameType::Wheel,
+            starts_at: at("2024-04-13T00:00:00Z"),
+            ends_at: at("2024-04-16T00:00:00Z"),
+            database_id: None,
+            limit: None,
+            prizes,
+        };
+        let settings = GameSettings {
+            campaigns: vec![
+                campaign("songkran", Some(vec![WheelPrize::new("Water gun", 1, true)])),
+                campaign("plain", None),
+            ],
+            ..Default::default()
+        };
+        let label = |game_type, number, campaign: Option<&str>| {
+            let spin_result = SpinResult { number, campaign: campaign.map(str::to_string), ..Default::default() };
+            settings.prize_label(game_type, &spin_result)
+        };
+
+        assert_eq!(label(GameType::Wheel, 0, None), "รับเครดิต 500");
+        assert_eq!(label(GameType::Wheel, 0, Some("songkran")), "Water gun");
+        assert_eq!(label(GameType::Wheel, 1, Some("songkran")), "unknown (1)");
+        // A campaign without its own prizes uses the regular wheel
+        assert_eq!(label(GameType::Wheel, 0, Some("plain")), "รับเครดิต 500");
+        // Nothing to read the number against once the campaign is removed from the config
+        assert_eq!(label(GameType::Wheel, 0, Some("gone")), "unknown (0)");
+        assert_eq!(label(GameType::Spin, 555, Some("songkran")), "555");
+    }
+
+    fn valid_result() -> SpinResult {
+        SpinResult {
+            key: "player-1@example.com".to_string(),
+            datetime: "2024-03-15T12:00:00+02:00".to_string(),
+            number: 42,
+            ..Default::default()
+        }
+    }
+
+    /// The fields `spin_result` fails validation on.
+    fn invalid_fields(spin_result: SpinResult) -> Vec<String> {
+        let mut fields: Vec<String> = match spin_result.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.field_errors().into_keys().map(|field| field.to_string()).collect(),
+        };
+        fields.sort();
+        fields
+    }
+
+    #[test]
+    fn valid_results_pass() {
+        assert!(valid_result().validate().is_ok());
+        assert!(SpinResult { key: "k".repeat(128), number: 999, ..valid_result() }.validate().is_ok());
+        assert!(SpinResult { key: "+31612345678".to_string(), number: 0, ..valid_result() }.validate().is_ok());
+    }
+
+    #[test]
+    fn keys_are_limited_in_length_and_charset() {
+        for key in ["", "has space", "semi;colon", "<script>", "new\nline"] {
+            assert_eq!(invalid_fields(SpinResult { key: key.to_string(), ..valid_result() }), ["key"], "{:?}", key);
+        }
+        assert_eq!(invalid_fields(SpinResult { key: "k".repeat(129), ..valid_result() }), ["key"]);
+    }
+
+    #[test]
+    fn datetimes_must_be_rfc3339_and_not_in_the_future() {
+        for datetime in ["2024-03-15", "2024-03-15 12:00:00", "15/03/2024 12:00", "yesterday", ""] {
+            assert_eq!(invalid_fields(SpinResult { datetime: datetime.to_string(), ..valid_result() }), ["datetime"], "{:?}", datetime);
+        }
+
+        let later = |secs: i64| (Utc::now() + Duration::seconds(secs)).to_rfc3339();
+        // Clocks drift, so a little ahead of the server still counts as now
+        assert!(SpinResult { datetime: later(60), ..valid_result() }.validate().is_ok());
+        assert_eq!(invalid_fields(SpinResult { datetime: later(CLOCK_SKEW_SECS + 60), ..valid_result() }), ["datetime"]);
+        // The offset does not matter, only the instant
+        let ahead = (Utc::now() + Duration::minutes(30)).with_timezone(&chrono::FixedOffset::east_opt(-5 * 3600).unwrap());
+        assert_eq!(invalid_fields(SpinResult { datetime: ahead.to_rfc3339(), ..valid_result() }), ["datetime"]);
+    }
+
+    #[test]
+    fn numbers_must_be_in_range() {
+        assert_eq!(invalid_fields(SpinResult { number: -1, ..valid_result() }), ["number"]);
+        assert_eq!(invalid_fields(SpinResult { number: 1000, ..valid_result() }), ["number"]);
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let spin_result = SpinResult {
+            key: "bad key".to_string(),
+            datetime: "soon".to_string(),
+            number: 5000,
+            fulfilled_at: Some("later".to_string()),
+            ..valid_result()
+        };
+        assert_eq!(invalid_fields(spin_result), ["datetime", "fulfilled_at", "key", "number"]);
+    }
+
+    #[test]
+    fn patches_only_check_the_fields_they_set() {
+        assert!(SpinResultPatch::default().validate().is_ok());
+        assert!(SpinResultPatch { number: Some(7), ..Default::default() }.validate().is_ok());
+
+        let patch = SpinResultPatch {
+            key: Some(String::new()),
+            datetime: Some("2024-03-15".to_string()),
+            number: Some(-3),
+            ..Default::default()
+        };
+        let errors = patch.validate().unwrap_err();
+        let mut fields: Vec<_> = errors.field_errors().into_keys().collect();
+        fields.sort();
+        assert_eq!(fields, ["datetime", "key", "number"]);
+    }
+
+    #[test]
+    fn page_ids_are_normalized_to_dashed_lowercase() {
+        let canonical = Some("0123abcd-4567-89ef-0123-456789abcdef".to_string());
+        assert_eq!(normalize_page_id("0123abcd456789ef0123456789abcdef"), canonical);
+        assert_eq!(normalize_page_id("0123ABCD-4567-89EF-0123-456789ABCDEF"), canonical);
+        assert_eq!(normalize_page_id(" 0123abcd456789ef0123456789abcdef "), canonical);
+    }
+
+    #[test]
+    fn malformed_page_ids_are_rejected() {
+        for page_id in [
+            "",
+            "0123abcd456789ef0123456789abcde",
+            "0123abcd456789ef0123456789abcdef0",
+            "0123abcd456789ef0123456789abcdeg",
+            "0123abcd4-567-89ef-0123-456789abcdef",
+            "0123abcd-4567-89ef-0123-456789abcdef-",
+            "0123-abcd456789ef0123456789abcdef",
+        ] {
+            assert_eq!(normalize_page_id(page_id), None, "{:?}", page_id);
+        }
+    }
+}
@@ -1,22 +1,58 @@
 use async_trait::async_trait;
-use crate::domain::models::{SpinResult, GameType};
+use chrono::{DateTime, Utc};
+use crate::domain::models::{SpinResult, SpinResultPatch, ArchivedResult, GameType, EntryFilter, EntryPage};
 
 #[async_trait]
-pub trait NotionRepository {
-    async fn create_entry(&self, spin_result: SpinResult, game_type: GameType) -> Result<(), Error>;
+pub trait NotionRepository: Send + Sync {
+    async fn create_entry(&self, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error>;
+    /// Like `create_entry`, but into a database other than the game's own, such as a campaign's.
+    async fn create_entry_in(&self, database_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error>;
+    async fn get_entry(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error>;
     async fn get_entries(&self, game_type: GameType) -> Result<Vec<SpinResult>, Error>;
-    async fn update_entry(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<(), Error>;
+    async fn find_entries(&self, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error>;
+    async fn find_entries_in(&self, database_id: &str, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error>;
+    async fn find_entries_page(&self, game_type: GameType, filter: &EntryFilter, cursor: Option<&str>) -> Result<EntryPage, Error>;
+    async fn get_entries_edited_since(&self, game_type: GameType, since: DateTime<Utc>) -> Result<Vec<SpinResult>, Error>;
+    async fn update_entry(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error>;
+    async fn patch_entry(&self, page_id: &str, patch: SpinResultPatch, game_type: GameType) -> Result<SpinResult, Error>;
     async fn delete_entry(&self, page_id: &str, game_type: GameType) -> Result<(), Error>;
+    async fn restore_entry(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error>;
+    async fn get_archived_entries(&self, game_type: GameType) -> Result<Vec<ArchivedResult>, Error>;
+    /// Counts a play, won or lost, in the UTC hour it happened.
+    async fn record_play(&self, game_type: GameType, played_at: DateTime<Utc>) -> Result<(), Error>;
+    /// Plays per UTC hour, oldest first, for the hours starting from the hour of `from` up to `to`.
+    async fn play_counts(&self, game_type: GameType, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<(DateTime<Utc>, u64)>, Error>;
+    /// Checks the API token is accepted.
+    async fn check_token(&self) -> Result<(), Error>;
+    /// Checks the database for `game_type` exists and is shared with the integration.
+    async fn check_database(&self, game_type: GameType) -> Result<(), Error>;
+    async fn check_database_in(&self, database_id: &str) -> Result<(), Error>;
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Daily spin limit reached")]
     SpinLimitReached,
+    #[error("Result not found")]
+    NotFound,
+    #[error("Invalid page id: {0}")]
+    InvalidPageId(String),
+    #[error("Prize has already been fulfilled")]
+    AlreadyFulfilled,
+    #[error("Unknown campaign: {0}")]
+    UnknownCampaign(String),
+    #[error("{0}")]
+    CampaignNotActive(String),
+    #[error("Invalid result: {0}")]
+    Invalid(#[from] validator::ValidationErrors),
     #[error("Notion API error: {0}")]
     NotionApi(String),
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
     #[error("HTTP client error: {0}")]
     HttpClient(#[from] reqwest::Error),
-} 
\ No newline at end of file
+    #[error("Not supported: {0}")]
+    Unsupported(String),
+    #[error("Mirror storage error: {0}")]
+    Storage(String),
+}
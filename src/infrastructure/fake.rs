@@ -0,0 +1,219 @@
+//! In-memory stand-in for Notion, for tests of the layers above the client.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+
+use crate::domain::{
+    models::{ArchivedResult, EntryFilter, EntryPage, GameType, SpinResult, SpinResultPatch},
+    repository::{Error, NotionRepository},
+};
+
+#[derive(Debug, Clone)]
+struct Page {
+    database_id: String,
+    game_type: GameType,
+    result: SpinResult,
+    archived: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    pages: Vec<Page>,
+    next_id: u32,
+    /// What the next query returns instead of the current pages, as when the
+    /// query started before the latest writes.
+    stale_query: Option<Vec<Page>>,
+}
+
+/// Pages live in one list; a game's own database is named after the game.
+#[derive(Debug, Clone, Default)]
+pub struct FakeNotion {
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeNotion {
+    /// Adds a page as if it had been created directly in Notion, last edited now
+    /// unless `last_edited_time` is set.
+    pub fn insert(&self, game_type: GameType, spin_result: SpinResult) -> SpinResult {
+        self.insert_in(game_type.as_str(), game_type, spin_result)
+    }
+
+    pub fn insert_in(&self, database_id: &str, game_type: GameType, mut spin_result: SpinResult) -> SpinResult {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        spin_result.page_id = Some(format!("00000000-0000-0000-0000-{:012x}", state.next_id));
+        spin_result.last_edited_time.get_or_insert_with(|| Utc::now().to_rfc3339());
+        spin_result.game_type = Some(game_type.as_str().to_string());
+        state.pages.push(Page {
+            database_id: database_id.to_string(),
+            game_type,
+            result: spin_result.clone(),
+            archived: false,
+        });
+        spin_result
+    }
+
+    /// Archives a page as if it had been deleted directly in Notion.
+    pub fn archive(&self, page_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(page) = state.pages.iter_mut().find(|page| page.result.page_id.as_deref() == Some(page_id)) {
+            page.archived = true;
+        }
+    }
+
+    /// Makes the next `get_entries` or `get_entries_edited_since` return the
+    /// pages as they are now, whatever happens before it is called.
+    pub fn snapshot_next_query(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stale_query = Some(state.pages.clone());
+    }
+
+    fn query(&self, database_id: &str, game_type: GameType, keep: impl Fn(&SpinResult) -> bool) -> Vec<SpinResult> {
+        let mut state = self.state.lock().unwrap();
+        let pages = state.stale_query.take().unwrap_or_else(|| state.pages.clone());
+        pages
+            .iter()
+            .filter(|page| !page.archived && page.database_id == database_id && page.game_type == game_type)
+            .map(|page| page.result.clone())
+            .filter(|spin_result| keep(spin_result))
+            .collect()
+    }
+
+    fn update(&self, page_id: &str, archived: bool, change: impl FnOnce(&mut SpinResult)) -> Result<SpinResult, Error> {
+        let mut state = self.state.lock().unwrap();
+        let page = state
+            .pages
+            .iter_mut()
+            .find(|page| page.result.page_id.as_deref() == Some(page_id) && page.archived == archived)
+            .ok_or(Error::NotFound)?;
+        change(&mut page.result);
+        page.result.last_edited_time = Some(Utc::now().to_rfc3339());
+        Ok(page.result.clone())
+    }
+}
+
+#[async_trait]
+impl NotionRepository for FakeNotion {
+    async fn create_entry(&self, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        Ok(self.insert(game_type, spin_result))
+    }
+
+    async fn create_entry_in(&self, database_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        Ok(self.insert_in(database_id, game_type, spin_result))
+    }
+
+    async fn get_entry(&self, page_id: &str, _game_type: GameType) -> Result<SpinResult, Error> {
+        let state = self.state.lock().unwrap();
+        state
+            .pages
+            .iter()
+            .find(|page| page.result.page_id.as_deref() == Some(page_id) && !page.archived)
+            .map(|page| page.result.clone())
+            .ok_or(Error::NotFound)
+    }
+
+    async fn get_entries(&self, game_type: GameType) -> Result<Vec<SpinResult>, Error> {
+        Ok(self.query(game_type.as_str(), game_type, |_| true))
+    }
+
+    async fn find_entries(&self, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        Ok(self.query(game_type.as_str(), game_type, |spin_result| filter.matches(spin_result)))
+    }
+
+    async fn find_entries_in(&self, database_id: &str, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        Ok(self.query(database_id, game_type, |spin_result| filter.matches(spin_result)))
+    }
+
+    async fn find_entries_page(&self, game_type: GameType, filter: &EntryFilter, _cursor: Option<&str>) -> Result<EntryPage, Error> {
+        Ok(EntryPage {
+            entries: self.find_entries(game_type, filter).await?,
+            next_cursor: None,
+        })
+    }
+
+    async fn get_entries_edited_since(&self, game_type: GameType, since: DateTime<Utc>) -> Result<Vec<SpinResult>, Error> {
+        Ok(self.query(game_type.as_str(), game_type, |spin_result| {
+            spin_result
+                .last_edited_time
+                .as_deref()
+                .and_then(|edited| DateTime::parse_from_rfc3339(edited).ok())
+                .is_some_and(|edited| edited >= since)
+        }))
+    }
+
+    async fn update_entry(&self, page_id: &str, spin_result: SpinResult, _game_type: GameType) -> Result<SpinResult, Error> {
+        self.update(page_id, false, |stored| {
+            *stored = SpinResult {
+                page_id: stored.page_id.take(),
+                game_type: stored.game_type.take(),
+                ..spin_result
+            }
+        })
+    }
+
+    async fn patch_entry(&self, page_id: &str, patch: SpinResultPatch, _game_type: GameType) -> Result<SpinResult, Error> {
+        self.update(page_id, false, |stored| {
+            if let Some(key) = patch.key {
+                stored.key = key;
+            }
+            if let Some(datetime) = patch.datetime {
+                stored.datetime = datetime;
+            }
+            if let Some(number) = patch.number {
+                stored.number = number;
+            }
+            if let Some(is_win) = patch.is_win {
+                stored.is_win = is_win;
+            }
+            if let Some(checked) = patch.checked {
+                stored.checked = checked;
+            }
+            if patch.fulfilled_by.is_some() {
+                stored.fulfilled_by = patch.fulfilled_by;
+            }
+            if patch.fulfilled_at.is_some() {
+                stored.fulfilled_at = patch.fulfilled_at;
+            }
+        })
+    }
+
+    async fn delete_entry(&self, page_id: &str, _game_type: GameType) -> Result<(), Error> {
+        self.update(page_id, false, |_| {})?;
+        self.archive(page_id);
+        Ok(())
+    }
+
+    async fn restore_entry(&self, page_id: &str, _game_type: GameType) -> Result<SpinResult, Error> {
+        let restored = self.update(page_id, true, |_| {})?;
+        let mut state = self.state.lock().unwrap();
+        if let Some(page) = state.pages.iter_mut().find(|page| page.result.page_id.as_deref() == Some(page_id)) {
+            page.archived = false;
+        }
+        Ok(restored)
+    }
+
+    async fn get_archived_entries(&self, _game_type: GameType) -> Result<Vec<ArchivedResult>, Error> {
+        Err(Error::Unsupported("Notion cannot list archived pages".to_string()))
+    }
+
+    async fn record_play(&self, _game_type: GameType, _played_at: DateTime<Utc>) -> Result<(), Error> {
+        Err(Error::Unsupported("Notion does not count plays".to_string()))
+    }
+
+    async fn play_counts(&self, _game_type: GameType, _from: Option<DateTime<Utc>>, _to: Option<DateTime<Utc>>) -> Result<Vec<(DateTime<Utc>, u64)>, Error> {
+        Err(Error::Unsupported("Notion does not count plays".to_string()))
+    }
+
+    async fn check_token(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn check_database(&self, _game_type: GameType) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn check_database_in(&self, _database_id: &str) -> Result<(), Error> {
+        Ok(())
+    }
+}
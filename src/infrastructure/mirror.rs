@@ -0,0 +1,786 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use rusqlite::{params, Connection};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{info, warn, debug};
+
+use crate::domain::{
+    models::{SpinResult, SpinResultPatch, ArchivedResult, GameType, EntryFilter, EntryPage},
+    repository::{NotionRepository, Error},
+};
+
+/// Notion rounds `last_edited_time` down to the minute, so incremental resyncs
+/// look back a little further than the point where the previous sync started.
+const RESYNC_OVERLAP_SECS: i64 = 120;
+const TRASH_RETENTION_DAYS: i64 = 30;
+
+#[derive(Default)]
+struct GameMirror {
+    entries: HashMap<String, SpinResult>,
+    // Results archived through the API. Notion cannot list archived pages itself.
+    trash: HashMap<String, ArchivedResult>,
+    // When pages were archived through the API, so a sync query that was already
+    // running and still returned them does not put them back
+    deleted: HashMap<String, DateTime<Utc>>,
+    // Plays, won or lost, by the UTC hour they started in. Notion only stores wins.
+    plays: BTreeMap<DateTime<Utc>, u64>,
+    synced_at: Option<DateTime<Utc>>,
+}
+
+impl GameMirror {
+    /// Drops pages archived since `since` from `spin_results`, forgetting
+    /// deletions too old to affect a query started after `since`.
+    fn without_deleted(&mut self, spin_results: Vec<SpinResult>, since: DateTime<Utc>) -> Vec<SpinResult> {
+        self.deleted.retain(|_, deleted_at| *deleted_at >= since);
+        spin_results
+            .into_iter()
+            .filter(|spin_result| spin_result.page_id.as_ref().is_some_and(|page_id| !self.deleted.contains_key(page_id)))
+            .collect()
+    }
+}
+
+/// Local copy of the Notion databases that serves reads and limit checks.
+///
+/// Writes go to Notion first and are applied to the mirror once Notion accepts
+/// them. Edits made directly in Notion are picked up by `resync`, and pages
+/// archived or deleted there by the periodic `full_sync`.
+#[derive(Clone)]
+pub struct MirroredRepository<R> {
+    inner: R,
+    games: Arc<RwLock<HashMap<GameType, GameMirror>>>,
+    store: Option<Arc<SqliteStore>>,
+}
+
+impl<R: NotionRepository + Clone + 'static> MirroredRepository<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            games: Arc::new(RwLock::new(HashMap::new())),
+            store: None,
+        }
+    }
+
+    /// Creates a mirror persisted to the SQLite file at `path`, loading whatever
+    /// was stored by a previous run.
+    pub fn with_sqlite(inner: R, path: &str) -> Result<Self, Error> {
+        let store = SqliteStore::open(path)?;
+        let mut games: HashMap<GameType, GameMirror> = HashMap::new();
+
+        for (game_type, spin_result) in store.load_entries()? {
+            if let Some(page_id) = spin_result.page_id.clone() {
+                games.entry(game_type).or_default().entries.insert(page_id, spin_result);
+            }
+        }
+        for (game_type, archived) in store.load_trash()? {
+            if let Some(page_id) = archived.result.page_id.clone() {
+                games.entry(game_type).or_default().trash.insert(page_id, archived);
+            }
+        }
+        for (game_type, synced_at) in store.load_sync_state()? {
+            games.entry(game_type).or_default().synced_at = Some(synced_at);
+        }
+        for (game_type, hour, count) in store.load_plays()? {
+            games.entry(game_type).or_default().plays.insert(hour, count);
+        }
+
+        info!("Loaded mirror from {} for {} game types", path, games.len());
+        Ok(Self {
+            inner,
+            games: Arc::new(RwLock::new(games)),
+            store: Some(Arc::new(store)),
+        })
+    }
+
+    /// Replaces the mirror for `game_type` with a full paginated query, dropping
+    /// pages that are no longer in the database.
+    pub async fn full_sync(&self, game_type: GameType) -> Result<usize, Error> {
+        let started_at = Utc::now();
+        let spin_results = self.inner.get_entries(game_type).await?;
+        let count = spin_results.len();
+
+        let entries = {
+            let mut games = self.games.write().unwrap();
+            let mirror = games.entry(game_type).or_default();
+            let recent = started_at - Duration::seconds(RESYNC_OVERLAP_SECS);
+            let mut entries: HashMap<String, SpinResult> = mirror
+                .without_deleted(spin_results, recent)
+                .into_iter()
+                .filter_map(|spin_result| spin_result.page_id.clone().map(|page_id| (page_id, spin_result)))
+                .collect();
+            // Keep writes applied while the query ran, which it may have missed
+            for (page_id, spin_result) in mirror.entries.drain() {
+                if edited_since(&spin_result, recent) {
+                    entries.entry(page_id).or_insert(spin_result);
+                }
+            }
+            mirror.entries = entries;
+            mirror.synced_at = Some(started_at);
+            mirror.entries.values().cloned().collect::<Vec<_>>()
+        };
+
+        if let Err(err) = self.persist(move |store| store.replace_entries(game_type, &entries, started_at)).await {
+            warn!("Failed to persist mirror for game type {:?}: {}", game_type, err);
+        }
+
+        info!("Mirrored {} results for game type: {:?}", count, game_type);
+        Ok(count)
+    }
+
+    /// Applies pages edited in Notion since the last sync.
+    ///
+    /// Pages archived directly in Notion are not returned by database queries,
+    /// so they are only dropped by the next `full_sync`.
+    pub async fn resync(&self, game_type: GameType) -> Result<usize, Error> {
+        let synced_at = self.games.read().unwrap().get(&game_type).and_then(|mirror| mirror.synced_at);
+        let Some(synced_at) = synced_at else {
+            return self.full_sync(game_type).await;
+        };
+
+        let started_at = Utc::now();
+        let since = synced_at - Duration::seconds(RESYNC_OVERLAP_SECS);
+        let spin_results = self.inner.get_entries_edited_since(game_type, since).await?;
+        let count = spin_results.len();
+
+        let applied = {
+            let mut games = self.games.write().unwrap();
+            let mirror = games.entry(game_type).or_default();
+            let spin_results = mirror.without_deleted(spin_results, started_at - Duration::seconds(RESYNC_OVERLAP_SECS));
+            for spin_result in &spin_results {
+                if let Some(page_id) = spin_result.page_id.clone() {
+                    mirror.entries.insert(page_id, spin_result.clone());
+                }
+            }
+            mirror.synced_at = Some(started_at);
+            spin_results
+        };
+
+        if let Err(err) = self.persist(move |store| store.upsert_entries(game_type, &applied, Some(started_at))).await {
+            warn!("Failed to persist mirror for game type {:?}: {}", game_type, err);
+        }
+
+        debug!("Resynced {} results for game type: {:?}", count, game_type);
+        Ok(count)
+    }
+
+    /// Runs a full sync for every game, logging failures instead of returning them
+    /// so one unreachable database does not block the others.
+    pub async fn sync_all(&self) {
+        for game_type in GameType::ALL {
+            if let Err(err) = self.full_sync(game_type).await {
+                warn!("Initial mirror sync failed for game type {:?}: {}", game_type, err);
+            }
+        }
+    }
+
+    /// Resyncs every `interval`, running a full sync instead once `full_interval`
+    /// has passed since the last one so pages removed in Notion are dropped.
+    pub fn spawn_resync(&self, interval: std::time::Duration, full_interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let mirror = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            let mut full_synced_at = tokio::time::Instant::now();
+            loop {
+                ticker.tick().await;
+                let full = full_synced_at.elapsed() >= full_interval;
+                for game_type in GameType::ALL {
+                    let synced = if full { mirror.full_sync(game_type).await } else { mirror.resync(game_type).await };
+                    if let Err(err) = synced {
+                        warn!("Mirror resync failed for game type {:?}: {}", game_type, err);
+                    }
+                }
+                if full {
+                    full_synced_at = tokio::time::Instant::now();
+                }
+            }
+        })
+    }
+
+    /// Runs `f` against the mirror for `game_type` once it has been synced at least once.
+    fn read_synced<T>(&self, game_type: GameType, f: impl FnOnce(&GameMirror) -> T) -> Option<T> {
+        let games = self.games.read().unwrap();
+        games
+            .get(&game_type)
+            .filter(|mirror| mirror.synced_at.is_some())
+            .map(f)
+    }
+
+    /// Runs `write` against the SQLite store, if there is one, on the blocking
+    /// thread pool so disk I/O does not stall requests.
+    async fn persist(&self, write: impl FnOnce(&SqliteStore) -> Result<(), Error> + Send + 'static) -> Result<(), Error> {
+        let Some(store) = self.store.clone() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || write(&store))
+            .await
+            .unwrap_or_else(|err| Err(Error::Storage(err.to_string())))
+    }
+
+    async fn apply_write(&self, game_type: GameType, spin_result: &SpinResult) {
+        let Some(page_id) = spin_result.page_id.clone() else {
+            return;
+        };
+
+        let persisted = spin_result.clone();
+        if let Err(err) = self.persist(move |store| store.upsert_entries(game_type, std::slice::from_ref(&persisted), None)).await {
+            warn!("Failed to persist result {} to mirror: {}", page_id, err);
+        }
+
+        self.games
+            .write()
+            .unwrap()
+            .entry(game_type)
+            .or_default()
+            .entries
+            .insert(page_id, spin_result.clone());
+    }
+
+    async fn apply_delete(&self, game_type: GameType, page_id: &str, archived: Option<SpinResult>) {
+        let now = Utc::now();
+        let archived = archived.map(|result| ArchivedResult {
+            result,
+            archived_at: now.to_rfc3339(),
+        });
+
+        let (removed, trashed) = (page_id.to_string(), archived.clone());
+        let persisted = self
+            .persist(move |store| {
+                store.remove_entry(&removed)?;
+                trashed.map_or(Ok(()), |archived| store.insert_trash(game_type, &archived))
+            })
+            .await;
+        if let Err(err) = persisted {
+            warn!("Failed to move result {} to the mirror trash: {}", page_id, err);
+        }
+
+        let mut games = self.games.write().unwrap();
+        let mirror = games.entry(game_type).or_default();
+        mirror.entries.remove(page_id);
+        mirror.deleted.insert(page_id.to_string(), now);
+        if let Some(archived) = archived {
+            mirror.trash.insert(page_id.to_string(), archived);
+        }
+    }
+
+    async fn apply_restore(&self, game_type: GameType, restored: &SpinResult) {
+        if let Some(page_id) = &restored.page_id {
+            let removed = page_id.clone();
+            if let Err(err) = self.persist(move |store| store.remove_trash(&removed)).await {
+                warn!("Failed to remove result {} from the mirror trash: {}", page_id, err);
+            }
+            if let Some(mirror) = self.games.write().unwrap().get_mut(&game_type) {
+                mirror.trash.remove(page_id);
+                mirror.deleted.remove(page_id);
+            }
+        }
+        self.apply_write(game_type, restored).await;
+    }
+}
+
+fn hour_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    at.duration_trunc(Duration::hours(1)).unwrap_or(at)
+}
+
+fn edited_since(spin_result: &SpinResult, since: DateTime<Utc>) -> bool {
+    spin_result
+        .last_edited_time
+        .as_deref()
+        .and_then(|edited| DateTime::parse_from_rfc3339(edited).ok())
+        .is_some_and(|edited| edited >= since)
+}
+
+fn sorted_newest_first(mut spin_results: Vec<SpinResult>) -> Vec<SpinResult> {
+    spin_results.sort_by_key(|spin_result| Reverse(spin_result.played_at()));
+    spin_results
+}
+
+#[async_trait]
+impl<R: NotionRepository + Clone + 'static> NotionRepository for MirroredRepository<R> {
+    async fn create_entry(&self, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        let created = self.inner.create_entry(spin_result, game_type).await?;
+        self.apply_write(game_type, &created).await;
+        Ok(created)
+    }
+
+    // Only the games' own databases are mirrored, other databases go straight to Notion
+    async fn create_entry_in(&self, database_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        self.inner.create_entry_in(database_id, spin_result, game_type).await
+    }
+
+    async fn get_entry(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error> {
+        // Single-page reads back write decisions, so always fetch the current page
+        let spin_result = self.inner.get_entry(page_id, game_type).await?;
+        self.apply_write(game_type, &spin_result).await;
+        Ok(spin_result)
+    }
+
+    async fn get_entries(&self, game_type: GameType) -> Result<Vec<SpinResult>, Error> {
+        match self.read_synced(game_type, |mirror| mirror.entries.values().cloned().collect()) {
+            Some(spin_results) => Ok(sorted_newest_first(spin_results)),
+            None => self.inner.get_entries(game_type).await,
+        }
+    }
+
+    async fn find_entries(&self, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        let local = self.read_synced(game_type, |mirror| {
+            mirror.entries
+                .values()
+                .filter(|spin_result| filter.matches(spin_result))
+                .cloned()
+                .collect()
+        });
+
+        match local {
+            Some(spin_results) => Ok(sorted_newest_first(spin_results)),
+            None => self.inner.find_entries(game_type, filter).await,
+        }
+    }
+
+    async fn find_entries_in(&self, database_id: &str, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        self.inner.find_entries_in(database_id, game_type, filter).await
+    }
+
+    async fn find_entries_page(&self, game_type: GameType, filter: &EntryFilter, cursor: Option<&str>) -> Result<EntryPage, Error> {
+        // Cursors are Notion's, so paging always goes to the source
+        self.inner.find_entries_page(game_type, filter, cursor).await
+    }
+
+    async fn get_entries_edited_since(&self, game_type: GameType, since: DateTime<Utc>) -> Result<Vec<SpinResult>, Error> {
+        self.inner.get_entries_edited_since(game_type, since).await
+    }
+
+    async fn update_entry(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        let updated = self.inner.update_entry(page_id, spin_result, game_type).await?;
+        self.apply_write(game_type, &updated).await;
+        Ok(updated)
+    }
+
+    async fn patch_entry(&self, page_id: &str, patch: SpinResultPatch, game_type: GameType) -> Result<SpinResult, Error> {
+        let updated = self.inner.patch_entry(page_id, patch, game_type).await?;
+        self.apply_write(game_type, &updated).await;
+        Ok(updated)
+    }
+
+    async fn delete_entry(&self, page_id: &str, game_type: GameType) -> Result<(), Error> {
+        // Keep a copy for the trash; fetch it when the mirror has not seen the page yet
+        let local = self.read_synced(game_type, |mirror| mirror.entries.get(page_id).cloned()).flatten();
+        let archived = match local {
+            Some(spin_result) => Some(spin_result),
+            None => self.inner.get_entry(page_id, game_type).await.ok(),
+        };
+
+        self.inner.delete_entry(page_id, game_type).await?;
+        self.apply_delete(game_type, page_id, archived).await;
+        Ok(())
+    }
+
+    async fn restore_entry(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error> {
+        let restored = self.inner.restore_entry(page_id, game_type).await?;
+        self.apply_restore(game_type, &restored).await;
+        Ok(restored)
+    }
+
+    async fn get_archived_entries(&self, game_type: GameType) -> Result<Vec<ArchivedResult>, Error> {
+        let cutoff = (Utc::now() - Duration::days(TRASH_RETENTION_DAYS)).to_rfc3339();
+
+        let mut archived: Vec<ArchivedResult> = self
+            .games
+            .read()
+            .unwrap()
+            .get(&game_type)
+            .map(|mirror| {
+                mirror.trash
+                    .values()
+                    .filter(|archived| archived.archived_at >= cutoff)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        archived.sort_by(|a, b| b.archived_at.cmp(&a.archived_at));
+        Ok(archived)
+    }
+
+    async fn record_play(&self, game_type: GameType, played_at: DateTime<Utc>) -> Result<(), Error> {
+        let hour = hour_start(played_at);
+        *self.games.write().unwrap().entry(game_type).or_default().plays.entry(hour).or_default() += 1;
+        self.persist(move |store| store.add_play(game_type, hour)).await
+    }
+
+    async fn play_counts(&self, game_type: GameType, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<(DateTime<Utc>, u64)>, Error> {
+        let from = from.map(hour_start);
+        let games = self.games.read().unwrap();
+        Ok(games
+            .get(&game_type)
+            .map(|mirror| {
+                mirror
+                    .plays
+                    .iter()
+                    .filter(|(hour, _)| from.is_none_or(|from| **hour >= from) && to.is_none_or(|to| **hour < to))
+                    .map(|(hour, count)| (*hour, *count))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn check_token(&self) -> Result<(), Error> {
+        self.inner.check_token().await
+    }
+
+    async fn check_database(&self, game_type: GameType) -> Result<(), Error> {
+        self.inner.check_database(game_type).await
+    }
+
+    async fn check_database_in(&self, database_id: &str) -> Result<(), Error> {
+        self.inner.check_database_in(database_id).await
+    }
+}
+
+struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Storage(err.to_string())
+    }
+}
+
+impl SqliteStore {
+    fn open(path: &str) -> Result<Self, Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                page_id TEXT PRIMARY KEY,
+                game_type TEXT NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS trash (
+                page_id TEXT PRIMARY KEY,
+                game_type TEXT NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sync_state (
+                game_type TEXT PRIMARY KEY,
+                synced_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS plays (
+                game_type TEXT NOT NULL,
+                hour TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (game_type, hour)
+            );",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn load_entries(&self) -> Result<Vec<(GameType, SpinResult)>, Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT game_type, data FROM entries")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (game_type, data) = row?;
+            let Ok(game_type) = game_type.parse::<GameType>() else {
+                continue;
+            };
+            entries.push((game_type, serde_json::from_str(&data)?));
+        }
+        Ok(entries)
+    }
+
+    fn load_trash(&self) -> Result<Vec<(GameType, ArchivedResult)>, Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT game_type, data FROM trash")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut trash = Vec::new();
+        for row in rows {
+            let (game_type, data) = row?;
+            let Ok(game_type) = game_type.parse::<GameType>() else {
+                continue;
+            };
+            trash.push((game_type, serde_json::from_str(&data)?));
+        }
+        Ok(trash)
+    }
+
+    fn load_sync_state(&self) -> Result<Vec<(GameType, DateTime<Utc>)>, Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT game_type, synced_at FROM sync_state")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut state = Vec::new();
+        for row in rows {
+            let (game_type, synced_at) = row?;
+            if let (Ok(game_type), Ok(synced_at)) = (game_type.parse::<GameType>(), DateTime::parse_from_rfc3339(&synced_at)) {
+                state.push((game_type, synced_at.with_timezone(&Utc)));
+            }
+        }
+        Ok(state)
+    }
+
+    fn load_plays(&self) -> Result<Vec<(GameType, DateTime<Utc>, u64)>, Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT game_type, hour, count FROM plays")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)))?;
+
+        let mut plays = Vec::new();
+        for row in rows {
+            let (game_type, hour, count) = row?;
+            if let (Ok(game_type), Ok(hour)) = (game_type.parse::<GameType>(), DateTime::parse_from_rfc3339(&hour)) {
+                plays.push((game_type, hour.with_timezone(&Utc), count as u64));
+            }
+        }
+        Ok(plays)
+    }
+
+    fn replace_entries(&self, game_type: GameType, spin_results: &[SpinResult], synced_at: DateTime<Utc>) -> Result<(), Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM entries WHERE game_type = ?1", params![game_type.as_str()])?;
+        Self::write_entries(&tx, game_type, spin_results, Some(synced_at))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn upsert_entries(&self, game_type: GameType, spin_results: &[SpinResult], synced_at: Option<DateTime<Utc>>) -> Result<(), Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::write_entries(&tx, game_type, spin_results, synced_at)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn write_entries(conn: &Connection, game_type: GameType, spin_results: &[SpinResult], synced_at: Option<DateTime<Utc>>) -> Result<(), Error> {
+        for spin_result in spin_results {
+            let Some(page_id) = &spin_result.page_id else {
+                continue;
+            };
+            conn.execute(
+                "INSERT OR REPLACE INTO entries (page_id, game_type, data) VALUES (?1, ?2, ?3)",
+                params![page_id, game_type.as_str(), serde_json::to_string(spin_result)?],
+            )?;
+        }
+        if let Some(synced_at) = synced_at {
+            conn.execute(
+                "INSERT OR REPLACE INTO sync_state (game_type, synced_at) VALUES (?1, ?2)",
+                params![game_type.as_str(), synced_at.to_rfc3339()],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn remove_entry(&self, page_id: &str) -> Result<(), Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM entries WHERE page_id = ?1", params![page_id])?;
+        Ok(())
+    }
+
+    fn insert_trash(&self, game_type: GameType, archived: &ArchivedResult) -> Result<(), Error> {
+        let Some(page_id) = &archived.result.page_id else {
+            return Ok(());
+        };
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO trash (page_id, game_type, data) VALUES (?1, ?2, ?3)",
+            params![page_id, game_type.as_str(), serde_json::to_string(archived)?],
+        )?;
+        Ok(())
+    }
+
+    fn remove_trash(&self, page_id: &str) -> Result<(), Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM trash WHERE page_id = ?1", params![page_id])?;
+        Ok(())
+    }
+
+    fn add_play(&self, game_type: GameType, hour: DateTime<Utc>) -> Result<(), Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO plays (game_type, hour, count) VALUES (?1, ?2, 1)
+             ON CONFLICT (game_type, hour) DO UPDATE SET count = count + 1",
+            params![game_type.as_str(), hour.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::fake::FakeNotion;
+
+    fn result(key: &str, datetime: &str) -> SpinResult {
+        SpinResult {
+            key: key.to_string(),
+            datetime: datetime.to_string(),
+            number: 7,
+            ..Default::default()
+        }
+    }
+
+    fn at(datetime: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(datetime).unwrap().with_timezone(&Utc)
+    }
+
+    async fn keys(mirror: &MirroredRepository<FakeNotion>) -> Vec<String> {
+        let mut keys: Vec<_> = mirror.get_entries(GameType::Spin).await.unwrap().into_iter().map(|spin_result| spin_result.key).collect();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn full_sync_drops_pages_archived_in_notion() {
+        let notion = FakeNotion::default();
+        let edited_long_ago = |spin_result| SpinResult { last_edited_time: Some("2024-03-01T12:00:00Z".to_string()), ..spin_result };
+        let kept = notion.insert(GameType::Spin, edited_long_ago(result("a", "2024-03-01T10:00:00Z")));
+        let archived = notion.insert(GameType::Spin, edited_long_ago(result("b", "2024-03-01T11:00:00Z")));
+        notion.insert(GameType::Wheel, result("c", "2024-03-01T12:00:00Z"));
+        let mirror = MirroredRepository::new(notion.clone());
+
+        assert_eq!(mirror.full_sync(GameType::Spin).await.unwrap(), 2);
+        assert_eq!(keys(&mirror).await, ["a", "b"]);
+
+        notion.archive(archived.page_id.as_deref().unwrap());
+        // Queries skip archived pages, so only a full sync notices
+        mirror.resync(GameType::Spin).await.unwrap();
+        assert_eq!(keys(&mirror).await, ["a", "b"]);
+        mirror.full_sync(GameType::Spin).await.unwrap();
+        assert_eq!(keys(&mirror).await, ["a"]);
+        assert_eq!(mirror.get_entries(GameType::Spin).await.unwrap()[0].page_id, kept.page_id);
+    }
+
+    #[tokio::test]
+    async fn full_sync_keeps_writes_its_query_missed() {
+        let notion = FakeNotion::default();
+        notion.insert(GameType::Spin, result("a", "2024-03-01T10:00:00Z"));
+        let mirror = MirroredRepository::new(notion.clone());
+        mirror.full_sync(GameType::Spin).await.unwrap();
+
+        notion.snapshot_next_query();
+        mirror.create_entry(result("b", "2024-03-01T11:00:00Z"), GameType::Spin).await.unwrap();
+        mirror.full_sync(GameType::Spin).await.unwrap();
+        assert_eq!(keys(&mirror).await, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn syncs_do_not_bring_back_pages_archived_while_they_ran() {
+        let notion = FakeNotion::default();
+        let a = notion.insert(GameType::Spin, result("a", "2024-03-01T10:00:00Z"));
+        let b = notion.insert(GameType::Spin, result("b", "2024-03-01T11:00:00Z"));
+        let mirror = MirroredRepository::new(notion.clone());
+        mirror.full_sync(GameType::Spin).await.unwrap();
+
+        notion.snapshot_next_query();
+        mirror.delete_entry(a.page_id.as_deref().unwrap(), GameType::Spin).await.unwrap();
+        mirror.full_sync(GameType::Spin).await.unwrap();
+        assert_eq!(keys(&mirror).await, ["b"]);
+
+        notion.snapshot_next_query();
+        mirror.delete_entry(b.page_id.as_deref().unwrap(), GameType::Spin).await.unwrap();
+        mirror.resync(GameType::Spin).await.unwrap();
+        assert!(keys(&mirror).await.is_empty());
+        assert_eq!(mirror.get_archived_entries(GameType::Spin).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn resync_applies_pages_edited_in_notion() {
+        let notion = FakeNotion::default();
+        let mirror = MirroredRepository::new(notion.clone());
+        mirror.full_sync(GameType::Spin).await.unwrap();
+
+        notion.insert(GameType::Spin, result("a", "2024-03-01T10:00:00Z"));
+        assert!(keys(&mirror).await.is_empty());
+        assert_eq!(mirror.resync(GameType::Spin).await.unwrap(), 1);
+        assert_eq!(keys(&mirror).await, ["a"]);
+    }
+
+    #[tokio::test]
+    async fn deleted_results_move_to_the_trash_and_back() {
+        let notion = FakeNotion::default();
+        let created = notion.insert(GameType::Spin, result("a", "2024-03-01T10:00:00Z"));
+        let page_id = created.page_id.as_deref().unwrap();
+        let mirror = MirroredRepository::new(notion);
+        mirror.full_sync(GameType::Spin).await.unwrap();
+
+        mirror.delete_entry(page_id, GameType::Spin).await.unwrap();
+        assert!(keys(&mirror).await.is_empty());
+        let trash = mirror.get_archived_entries(GameType::Spin).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].result.page_id.as_deref(), Some(page_id));
+
+        mirror.restore_entry(page_id, GameType::Spin).await.unwrap();
+        assert_eq!(keys(&mirror).await, ["a"]);
+        assert!(mirror.get_archived_entries(GameType::Spin).await.unwrap().is_empty());
+        // A restored page is synced again like any other
+        mirror.full_sync(GameType::Spin).await.unwrap();
+        assert_eq!(keys(&mirror).await, ["a"]);
+    }
+
+    #[tokio::test]
+    async fn entries_are_newest_first_whatever_their_offset() {
+        let notion = FakeNotion::default();
+        notion.insert(GameType::Spin, result("utc", "2024-03-01T10:00:00Z"));
+        // 09:00 UTC, though the string sorts after the one above
+        notion.insert(GameType::Spin, result("bangkok", "2024-03-01T16:00:00+07:00"));
+        notion.insert(GameType::Spin, result("unparsed", "yesterday"));
+        let mirror = MirroredRepository::new(notion);
+        mirror.full_sync(GameType::Spin).await.unwrap();
+
+        let keys: Vec<_> = mirror.get_entries(GameType::Spin).await.unwrap().into_iter().map(|spin_result| spin_result.key).collect();
+        assert_eq!(keys, ["utc", "bangkok", "unparsed"]);
+    }
+
+    #[tokio::test]
+    async fn plays_are_counted_per_hour() {
+        let mirror = MirroredRepository::new(FakeNotion::default());
+        for played_at in ["2024-03-01T09:59:59Z", "2024-03-01T10:00:00Z", "2024-03-01T10:59:00Z", "2024-03-01T18:30:00+07:00"] {
+            mirror.record_play(GameType::Spin, at(played_at)).await.unwrap();
+        }
+        mirror.record_play(GameType::Wheel, at("2024-03-01T10:00:00Z")).await.unwrap();
+
+        let counts = mirror.play_counts(GameType::Spin, None, None).await.unwrap();
+        assert_eq!(counts, [(at("2024-03-01T09:00:00Z"), 1), (at("2024-03-01T10:00:00Z"), 2), (at("2024-03-01T11:00:00Z"), 1)]);
+        // `from` is taken back to the start of its hour, `to` is exclusive
+        let counts = mirror.play_counts(GameType::Spin, Some(at("2024-03-01T10:30:00Z")), Some(at("2024-03-01T11:00:00Z"))).await.unwrap();
+        assert_eq!(counts, [(at("2024-03-01T10:00:00Z"), 2)]);
+    }
+
+    #[tokio::test]
+    async fn unsynced_games_are_read_from_notion() {
+        let notion = FakeNotion::default();
+        notion.insert(GameType::Spin, result("a", "2024-03-01T10:00:00Z"));
+        let mirror = MirroredRepository::new(notion);
+        assert_eq!(keys(&mirror).await, ["a"]);
+    }
+
+    #[tokio::test]
+    async fn sqlite_store_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mirror.db");
+        let path = path.to_str().unwrap();
+
+        let notion = FakeNotion::default();
+        notion.insert(GameType::Spin, result("a", "2024-03-01T10:00:00Z"));
+        let archived = notion.insert(GameType::Spin, result("b", "2024-03-01T11:00:00Z"));
+        let mirror = MirroredRepository::with_sqlite(notion, path).unwrap();
+        mirror.full_sync(GameType::Spin).await.unwrap();
+        mirror.create_entry(result("c", "2024-03-01T12:00:00Z"), GameType::Spin).await.unwrap();
+        mirror.delete_entry(archived.page_id.as_deref().unwrap(), GameType::Spin).await.unwrap();
+        let played_at = at("2024-03-01T12:30:00Z");
+        mirror.record_play(GameType::Spin, played_at).await.unwrap();
+        mirror.record_play(GameType::Spin, played_at).await.unwrap();
+        drop(mirror);
+
+        // Nothing left in Notion, so everything read back comes from the file
+        let reopened = MirroredRepository::with_sqlite(FakeNotion::default(), path).unwrap();
+        assert_eq!(keys(&reopened).await, ["a", "c"]);
+        let trash = reopened.get_archived_entries(GameType::Spin).await.unwrap();
+        assert_eq!(trash.iter().map(|archived| archived.result.key.as_str()).collect::<Vec<_>>(), ["b"]);
+        assert!(reopened.games.read().unwrap()[&GameType::Spin].synced_at.is_some());
+        assert_eq!(reopened.play_counts(GameType::Spin, None, None).await.unwrap(), [(at("2024-03-01T12:00:00Z"), 2)]);
+    }
+}
@@ -1 +1,4 @@
-pub mod notion;
\ No newline at end of file
+pub mod notion;
+pub mod mirror;
+#[cfg(test)]
+pub mod fake;
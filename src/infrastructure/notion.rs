@@ -1,269 +1,589 @@
 use async_trait::async_trait;
-use reqwest::Client;
-use serde_json::json;
-use chrono::Utc;
+use reqwest::{Client, Method, RequestBuilder};
+use serde_json::{json, Map, Value};
+use chrono::{DateTime, SecondsFormat, Utc};
 use tracing::{info, warn, error, debug};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::{sync::Mutex, time::Instant};
 
 use crate::domain::{
     models::*,
     repository::{NotionRepository, Error},
 };
+use crate::metrics;
+
+const NOTION_API_URL: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+const PAGE_SIZE: u32 = 100;
+// Notion allows an average of three requests per second per integration
+const DEFAULT_REQUESTS_PER_SECOND: u32 = 3;
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+/// Spaces requests evenly so bursts (bulk operations, imports) stay under Notion's rate limit.
+struct Throttle {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(requests_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests_per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn wait(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
 
 #[derive(Clone)]
 pub struct NotionClient {
     client: Client,
     database_ids: HashMap<GameType, String>,
     api_token: String,
-    daily_spin_limit: i32,
+    throttle: Arc<Throttle>,
 }
 
 impl NotionClient {
-    pub fn new(database_ids: HashMap<GameType, String>, api_token: String, daily_spin_limit: i32) -> Self {
+    pub fn new(database_ids: HashMap<GameType, String>, api_token: String) -> Self {
         let client = Client::new();
         Self {
             client,
             database_ids,
             api_token,
-            daily_spin_limit,
+            throttle: Arc::new(Throttle::new(DEFAULT_REQUESTS_PER_SECOND)),
         }
     }
 
+    pub fn with_requests_per_second(mut self, requests_per_second: u32) -> Self {
+        self.throttle = Arc::new(Throttle::new(requests_per_second));
+        self
+    }
+
+    /// Gives up on a Notion request that has not completed within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to build HTTP client");
+        self
+    }
+
     fn get_database_id(&self, game_type: GameType) -> Result<&String, Error> {
         self.database_ids.get(&game_type)
             .ok_or_else(|| Error::NotionApi(format!("No database ID configured for game type: {:?}", game_type)))
     }
 
-    fn build_properties(&self, spin_result: &SpinResult) -> NotionProperties {
-        // Convert game_type to string if present
-        let _game_type_str = spin_result.game_type.clone().unwrap_or_default();
-        
-        NotionProperties {
-            key: NotionTitle {
-                r#type: "title".to_string(),
-                title: vec![NotionText {
-                    r#type: "text".to_string(),
-                    text: NotionTextContent {
-                        content: spin_result.key.clone(),
-                    },
-                }],
-            },
-            datetime: NotionDate {
-                r#type: "date".to_string(),
-                date: NotionDateContent {
-                    start: spin_result.datetime.clone(),
-                },
-            },
-            number: NotionNumber {
-                r#type: "number".to_string(),
-                number: spin_result.number,
-            },
-            is_win: NotionCheckbox {
-                r#type: "checkbox".to_string(),
-                checkbox: spin_result.is_win,
-            },
-            checked: NotionCheckbox {
-                r#type: "checkbox".to_string(),
-                checkbox: spin_result.checked,
-            },
+    /// Fetches a page and checks it lives in the database configured for `game_type`,
+    /// so ids from unrelated databases the integration can see are treated as missing.
+    async fn get_owned_page(&self, page_id: &str, game_type: GameType) -> Result<Value, Error> {
+        let database_id = self.get_database_id(game_type)?;
+        let page = self.send(self.request(Method::GET, &format!("/pages/{}", page_id))).await?;
+
+        let parent_id = page["parent"]["database_id"].as_str().and_then(normalize_page_id);
+        match (parent_id, normalize_page_id(database_id)) {
+            (Some(parent_id), Some(database_id)) if parent_id == database_id => Ok(page),
+            _ => {
+                warn!("Page {} does not belong to the {:?} database", page_id, game_type);
+                Err(Error::NotFound)
+            }
         }
     }
 
-    async fn has_reached_spin_limit(&self, key: &str, game_type: GameType) -> Result<bool, Error> {
-        debug!("Checking spin limit for key: {} with game type: {:?}", key, game_type);
-        let today = Utc::now().date_naive();
-        let database_id = self.get_database_id(game_type)?;
-        
-        let response = self.client
-            .post(format!("https://api.notion.com/v1/databases/{}/query", database_id))
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Notion-Version", "2022-06-28")
-            .json(&json!({
-                "filter": {
-                    "and": [
-                        {
-                            "property": "key",
-                            "title": {
-                                "equals": key
-                            }
-                        },
-                        {
-                            "property": "datetime",
-                            "date": {
-                                "after": format!("{}T00:00:00Z", today),
-                                "before": format!("{}T23:59:59Z", today)
-                            }
-                        }
-                    ]
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", NOTION_API_URL, path))
+            // Marks the header sensitive, so it is redacted wherever the request is debug-printed
+            .bearer_auth(&self.api_token)
+            .header("Notion-Version", NOTION_VERSION)
+    }
+
+    /// Sends a request once a throttle slot is free, retrying when Notion answers 429.
+    async fn send(&self, request: RequestBuilder) -> Result<Value, Error> {
+        let (method, path, endpoint) = match request.try_clone().and_then(|request| request.build().ok()) {
+            Some(built) => (
+                built.method().to_string(),
+                built.url().path().to_string(),
+                Self::endpoint_label(built.method(), built.url()),
+            ),
+            None => Default::default(),
+        };
+
+        let mut attempt = 0;
+        let response = loop {
+            // Every request built by `request` has a buffered JSON body, so cloning succeeds
+            let attempt_request = request
+                .try_clone()
+                .ok_or_else(|| Error::NotionApi("Request cannot be retried".to_string()))?;
+            self.throttle.wait().await;
+            let started = Instant::now();
+            let response = match attempt_request.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    info!(method, path, duration_ms = started.elapsed().as_millis() as u64, error = %err, "Notion request failed");
+                    metrics::observe_notion_request(&endpoint, "error", started.elapsed());
+                    metrics::record_notion_error(if err.is_timeout() { "timeout" } else { "request_failed" });
+                    return Err(err.into());
                 }
-            }))
-            .send()
-            .await?;
+            };
+            info!(method, path, status = response.status().as_u16(), duration_ms = started.elapsed().as_millis() as u64, "Notion request");
+            metrics::observe_notion_request(&endpoint, response.status().as_str(), started.elapsed());
+
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS || attempt >= MAX_RATE_LIMITED_RETRIES {
+                break response;
+            }
+            metrics::record_notion_error("rate_limited");
+
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(1);
+            attempt += 1;
+            warn!("Notion API rate limited, retrying in {}s (attempt {})", retry_after, attempt);
+            tokio::time::sleep(Duration::from_secs(retry_after)).await;
+        };
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            let error_text = response.text().await?;
+            metrics::record_notion_error(&Self::error_code(&error_text, reqwest::StatusCode::NOT_FOUND));
+            debug!("Notion API returned 404: {}", error_text);
+            return Err(Error::NotFound);
+        }
 
         if !response.status().is_success() {
-            return Err(Error::NotionApi(response.text().await?));
+            let status = response.status();
+            let error_text = response.text().await?;
+            metrics::record_notion_error(&Self::error_code(&error_text, status));
+            error!("Notion API error: {}", error_text);
+            return Err(Error::NotionApi(error_text));
         }
 
-        let data: serde_json::Value = response.json().await?;
+        Ok(response.json().await?)
+    }
+
+    /// Method and path of a Notion call with ids replaced, such as `POST /databases/:id/query`,
+    /// so each endpoint is a single metrics series.
+    fn endpoint_label(method: &Method, url: &reqwest::Url) -> String {
+        let path: Vec<&str> = url
+            .path()
+            .trim_start_matches("/v1")
+            .split('/')
+            .map(|segment| if normalize_page_id(segment).is_some() { ":id" } else { segment })
+            .collect();
+        format!("{} {}", method, path.join("/"))
+    }
+
+    /// The `code` Notion puts in error bodies, such as `validation_error`, or the status if there is none.
+    fn error_code(error_text: &str, status: reqwest::StatusCode) -> String {
+        serde_json::from_str::<Value>(error_text)
+            .ok()
+            .and_then(|body| body["code"].as_str().map(str::to_string))
+            .unwrap_or_else(|| status.as_str().to_string())
+    }
+
+    /// Runs a database query and follows `next_cursor` until every page has been read.
+    async fn query_database(&self, database_id: &str, filter: Option<Value>) -> Result<Vec<Value>, Error> {
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let (results, next_cursor) = self.query_page(database_id, filter.as_ref(), cursor.as_deref()).await?;
+            pages.extend(results);
+
+            cursor = match next_cursor {
+                Some(next) => Some(next),
+                None => break,
+            };
+        }
+
+        Ok(pages)
+    }
+
+    /// Fetches a single page of a database query, returning the cursor for the next one.
+    async fn query_page(&self, database_id: &str, filter: Option<&Value>, cursor: Option<&str>) -> Result<(Vec<Value>, Option<String>), Error> {
+        let path = format!("/databases/{}/query", database_id);
+
+        let mut body = json!({ "page_size": PAGE_SIZE });
+        if let Some(filter) = filter {
+            body["filter"] = filter.clone();
+        }
+        if let Some(cursor) = cursor {
+            body["start_cursor"] = json!(cursor);
+        }
+
+        let data = self.send(self.request(Method::POST, &path).json(&body)).await?;
         let results = data["results"].as_array()
-            .ok_or_else(|| Error::NotionApi("Invalid response format".to_string()))?;
+            .ok_or_else(|| Error::NotionApi("Invalid response format".to_string()))?
+            .clone();
+
+        let next_cursor = match (data["has_more"].as_bool(), data["next_cursor"].as_str()) {
+            (Some(true), Some(next)) => Some(next.to_string()),
+            _ => None,
+        };
+        Ok((results, next_cursor))
+    }
+
+    fn build_filter(filter: &EntryFilter) -> Option<Value> {
+        let mut conditions = Vec::new();
+
+        if let Some(key) = &filter.key {
+            conditions.push(json!({
+                "property": "key",
+                "title": { "equals": key }
+            }));
+        }
+        if let Some(from) = filter.from {
+            conditions.push(json!({
+                "property": "datetime",
+                "date": { "on_or_after": from.to_rfc3339_opts(SecondsFormat::Secs, true) }
+            }));
+        }
+        if let Some(to) = filter.to {
+            conditions.push(json!({
+                "property": "datetime",
+                "date": { "before": to.to_rfc3339_opts(SecondsFormat::Secs, true) }
+            }));
+        }
+        if let Some(is_win) = filter.is_win {
+            conditions.push(json!({
+                "property": "is_win",
+                "checkbox": { "equals": is_win }
+            }));
+        }
+        if let Some(checked) = filter.checked {
+            conditions.push(json!({
+                "property": "checked",
+                "checkbox": { "equals": checked }
+            }));
+        }
+
+        match conditions.len() {
+            0 => None,
+            1 => conditions.pop(),
+            _ => Some(json!({ "and": conditions })),
+        }
+    }
+
+    fn title(content: &str) -> NotionTitle {
+        NotionTitle {
+            r#type: "title".to_string(),
+            title: vec![Self::text(content)],
+        }
+    }
+
+    fn rich_text(content: &str) -> NotionRichText {
+        NotionRichText {
+            r#type: "rich_text".to_string(),
+            rich_text: vec![Self::text(content)],
+        }
+    }
+
+    fn text(content: &str) -> NotionText {
+        NotionText {
+            r#type: "text".to_string(),
+            text: NotionTextContent {
+                content: content.to_string(),
+            },
+        }
+    }
+
+    fn date(start: &str) -> NotionDate {
+        NotionDate {
+            r#type: "date".to_string(),
+            date: NotionDateContent {
+                start: start.to_string(),
+            },
+        }
+    }
+
+    fn number(number: i32) -> NotionNumber {
+        NotionNumber {
+            r#type: "number".to_string(),
+            number,
+        }
+    }
+
+    fn checkbox(checkbox: bool) -> NotionCheckbox {
+        NotionCheckbox {
+            r#type: "checkbox".to_string(),
+            checkbox,
+        }
+    }
+
+    fn build_properties(&self, spin_result: &SpinResult) -> NotionProperties {
+        NotionProperties {
+            key: Self::title(&spin_result.key),
+            datetime: Self::date(&spin_result.datetime),
+            number: Self::number(spin_result.number),
+            is_win: Self::checkbox(spin_result.is_win),
+            checked: Self::checkbox(spin_result.checked),
+            fulfilled_by: spin_result.fulfilled_by.as_deref().map(Self::rich_text),
+            fulfilled_at: spin_result.fulfilled_at.as_deref().map(Self::date),
+            campaign: spin_result.campaign.as_deref().map(Self::rich_text),
+        }
+    }
+
+    /// Builds a `properties` object containing only the properties set in `patch`.
+    fn build_patch_properties(&self, patch: &SpinResultPatch) -> Result<Map<String, Value>, Error> {
+        let mut properties = Map::new();
+
+        if let Some(key) = &patch.key {
+            properties.insert("key".to_string(), serde_json::to_value(Self::title(key))?);
+        }
+        if let Some(datetime) = &patch.datetime {
+            properties.insert("datetime".to_string(), serde_json::to_value(Self::date(datetime))?);
+        }
+        if let Some(number) = patch.number {
+            properties.insert("number".to_string(), serde_json::to_value(Self::number(number))?);
+        }
+        if let Some(is_win) = patch.is_win {
+            properties.insert("is_win".to_string(), serde_json::to_value(Self::checkbox(is_win))?);
+        }
+        if let Some(checked) = patch.checked {
+            properties.insert("checked".to_string(), serde_json::to_value(Self::checkbox(checked))?);
+        }
+        if let Some(fulfilled_by) = &patch.fulfilled_by {
+            properties.insert("fulfilled_by".to_string(), serde_json::to_value(Self::rich_text(fulfilled_by))?);
+        }
+        if let Some(fulfilled_at) = &patch.fulfilled_at {
+            properties.insert("fulfilled_at".to_string(), serde_json::to_value(Self::date(fulfilled_at))?);
+        }
 
-        let count = results.len();
-        debug!("Found {} spins today for key: {} with game type: {:?}", count, key, game_type);
-        Ok(count >= self.daily_spin_limit as usize)
+        Ok(properties)
+    }
+
+    fn parse_page(page: &Value, game_type: GameType) -> SpinResult {
+        let properties = &page["properties"];
+
+        let key = properties["key"]["title"][0]["text"]["content"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+
+        let datetime = properties["datetime"]["date"]["start"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+
+        let number = properties["number"]["number"]
+            .as_i64()
+            .unwrap_or(0) as i32;
+
+        // Databases created by `create_database` use `is_win`; older ones use `isWin`
+        let is_win = properties["is_win"]["checkbox"]
+            .as_bool()
+            .or_else(|| properties["isWin"]["checkbox"].as_bool())
+            .unwrap_or(false);
+
+        let checked = properties["checked"]["checkbox"]
+            .as_bool()
+            .unwrap_or(false);
+
+        let fulfilled_by = properties["fulfilled_by"]["rich_text"][0]["text"]["content"]
+            .as_str()
+            .map(str::to_string);
+
+        let fulfilled_at = properties["fulfilled_at"]["date"]["start"]
+            .as_str()
+            .map(str::to_string);
+
+        let campaign = properties["campaign"]["rich_text"][0]["text"]["content"]
+            .as_str()
+            .map(str::to_string);
+
+        SpinResult {
+            key,
+            datetime,
+            number,
+            is_win,
+            checked,
+            game_type: Some(format!("{:?}", game_type)),
+            page_id: page["id"].as_str().map(str::to_string),
+            last_edited_time: page["last_edited_time"].as_str().map(str::to_string),
+            fulfilled_by,
+            fulfilled_at,
+            campaign,
+        }
     }
 }
 
 #[async_trait]
 impl NotionRepository for NotionClient {
-    async fn create_entry(&self, spin_result: SpinResult, game_type: GameType) -> Result<(), Error> {
+    async fn create_entry(&self, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
+        let database_id = self.get_database_id(game_type)?;
+        self.create_entry_in(database_id, spin_result, game_type).await
+    }
+
+    async fn create_entry_in(&self, database_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
         info!(
-            "Creating new result for key {} with number {} for game type: {:?}",
-            spin_result.key, spin_result.number, game_type
+            "Creating new result for key {} with number {} for game type: {:?} in database {}",
+            spin_result.key, spin_result.number, game_type, database_id
         );
 
-        if self.has_reached_spin_limit(&spin_result.key, game_type).await? {
-            warn!(
-                "Daily limit reached for key: {} with game type: {:?}",
-                spin_result.key, game_type
-            );
-            return Err(Error::SpinLimitReached);
-        }
-
-        let database_id = self.get_database_id(game_type)?;
         let properties = self.build_properties(&spin_result);
-        
-        let response = self.client
-            .post("https://api.notion.com/v1/pages")
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Notion-Version", "2022-06-28")
-            .json(&json!({
-                "parent": { "database_id": database_id },
-                "properties": properties
-            }))
-            .send()
-            .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            error!("Notion API error: {}", error_text);
-            return Err(Error::NotionApi(error_text));
-        }
+        let page = self.send(self.request(Method::POST, "/pages").json(&json!({
+            "parent": { "database_id": database_id },
+            "properties": properties
+        }))).await?;
 
         info!("Successfully created result for game type: {:?}", game_type);
-        Ok(())
+        Ok(Self::parse_page(&page, game_type))
     }
 
-    async fn get_entries(&self, game_type: GameType) -> Result<Vec<SpinResult>, Error> {
-        debug!("Fetching all results for game type: {:?}", game_type);
-        let database_id = self.get_database_id(game_type)?;
-        
-        let response = self.client
-            .post(format!("https://api.notion.com/v1/databases/{}/query", database_id))
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Notion-Version", "2022-06-28")
-            .json(&json!({
-                "page_size": 100
-            }))
-            .send()
-            .await?;
+    async fn get_entry(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error> {
+        debug!("Fetching result {} for game type: {:?}", page_id, game_type);
 
-        if !response.status().is_success() {
-            return Err(Error::NotionApi(response.text().await?));
+        let page = self.get_owned_page(page_id, game_type).await?;
+        if page["archived"].as_bool().unwrap_or(false) {
+            return Err(Error::NotFound);
         }
 
-        let data: serde_json::Value = response.json().await?;
-        let results = data["results"].as_array()
-            .ok_or_else(|| Error::NotionApi("Invalid response format".to_string()))?;
-
-        let mut spin_results = Vec::new();
-        for result in results {
-            let properties = &result["properties"];
-            
-            let key = properties["key"]["title"][0]["text"]["content"]
-                .as_str()
-                .unwrap_or("")
-                .to_string();
-            
-            let datetime = properties["datetime"]["date"]["start"]
-                .as_str()
-                .unwrap_or("")
-                .to_string();
-            
-            let number = properties["number"]["number"]
-                .as_i64()
-                .unwrap_or(0) as i32;
-            
-            let is_win = properties["isWin"]["checkbox"]
-                .as_bool()
-                .unwrap_or(false);
-                
-            let checked = properties["checked"]["checkbox"]
-                .as_bool()
-                .unwrap_or(false);
-
-            spin_results.push(SpinResult {
-                key,
-                datetime,
-                number,
-                is_win,
-                checked,
-                game_type: Some(format!("{:?}", game_type)),
-            });
-        }
+        Ok(Self::parse_page(&page, game_type))
+    }
+
+    async fn get_entries(&self, game_type: GameType) -> Result<Vec<SpinResult>, Error> {
+        debug!("Fetching all results for game type: {:?}", game_type);
+
+        let pages = self.query_database(self.get_database_id(game_type)?, None).await?;
+        let spin_results: Vec<SpinResult> = pages
+            .iter()
+            .map(|page| Self::parse_page(page, game_type))
+            .collect();
 
         info!("Successfully fetched {} results for game type: {:?}", spin_results.len(), game_type);
         Ok(spin_results)
     }
 
-    async fn update_entry(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<(), Error> {
+    async fn find_entries(&self, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        let database_id = self.get_database_id(game_type)?;
+        self.find_entries_in(database_id, game_type, filter).await
+    }
+
+    async fn find_entries_in(&self, database_id: &str, game_type: GameType, filter: &EntryFilter) -> Result<Vec<SpinResult>, Error> {
+        debug!("Querying results for game type: {:?} in database {} with filter: {:?}", game_type, database_id, filter);
+
+        let pages = self.query_database(database_id, Self::build_filter(filter)).await?;
+        Ok(pages.iter().map(|page| Self::parse_page(page, game_type)).collect())
+    }
+
+    async fn find_entries_page(&self, game_type: GameType, filter: &EntryFilter, cursor: Option<&str>) -> Result<EntryPage, Error> {
+        debug!("Querying a page of results for game type: {:?} with filter: {:?}", game_type, filter);
+
+        let (pages, next_cursor) = self.query_page(self.get_database_id(game_type)?, Self::build_filter(filter).as_ref(), cursor).await?;
+        Ok(EntryPage {
+            entries: pages.iter().map(|page| Self::parse_page(page, game_type)).collect(),
+            next_cursor,
+        })
+    }
+
+    async fn get_entries_edited_since(&self, game_type: GameType, since: DateTime<Utc>) -> Result<Vec<SpinResult>, Error> {
+        debug!("Fetching results edited since {} for game type: {:?}", since, game_type);
+
+        let filter = json!({
+            "timestamp": "last_edited_time",
+            "last_edited_time": { "on_or_after": since.to_rfc3339_opts(SecondsFormat::Secs, true) }
+        });
+        let pages = self.query_database(self.get_database_id(game_type)?, Some(filter)).await?;
+        Ok(pages.iter().map(|page| Self::parse_page(page, game_type)).collect())
+    }
+
+    async fn update_entry(&self, page_id: &str, spin_result: SpinResult, game_type: GameType) -> Result<SpinResult, Error> {
         info!(
             "Updating result {} for key {} with number {} for game type: {:?}",
             page_id, spin_result.key, spin_result.number, game_type
         );
-        
+
+        self.get_owned_page(page_id, game_type).await?;
         let properties = self.build_properties(&spin_result);
-        
-        let response = self.client
-            .patch(format!("https://api.notion.com/v1/pages/{}", page_id))
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Notion-Version", "2022-06-28")
-            .json(&json!({
-                "properties": properties
-            }))
-            .send()
-            .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            error!("Failed to update result {}: {}", page_id, error_text);
-            return Err(Error::NotionApi(error_text));
-        }
+        let page = self.send(self.request(Method::PATCH, &format!("/pages/{}", page_id)).json(&json!({
+            "properties": properties
+        }))).await.inspect_err(|_| error!("Failed to update result {}", page_id))?;
 
         info!("Successfully updated result {} for game type: {:?}", page_id, game_type);
-        Ok(())
+        Ok(Self::parse_page(&page, game_type))
+    }
+
+    async fn patch_entry(&self, page_id: &str, patch: SpinResultPatch, game_type: GameType) -> Result<SpinResult, Error> {
+        info!("Patching result {} for game type: {:?} with {:?}", page_id, game_type, patch);
+
+        self.get_owned_page(page_id, game_type).await?;
+        let properties = self.build_patch_properties(&patch)?;
+
+        let page = self.send(self.request(Method::PATCH, &format!("/pages/{}", page_id)).json(&json!({
+            "properties": properties
+        }))).await.inspect_err(|_| error!("Failed to patch result {}", page_id))?;
+
+        info!("Successfully patched result {} for game type: {:?}", page_id, game_type);
+        Ok(Self::parse_page(&page, game_type))
     }
 
     async fn delete_entry(&self, page_id: &str, game_type: GameType) -> Result<(), Error> {
         info!("Deleting result {} for game type: {:?}", page_id, game_type);
-        
-        let response = self.client
-            .patch(format!("https://api.notion.com/v1/pages/{}", page_id))
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Notion-Version", "2022-06-28")
-            .json(&json!({
-                "archived": true
-            }))
-            .send()
-            .await?;
+        self.get_owned_page(page_id, game_type).await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            error!("Failed to delete result {}: {}", page_id, error_text);
-            return Err(Error::NotionApi(error_text));
-        }
+        self.send(self.request(Method::PATCH, &format!("/pages/{}", page_id)).json(&json!({
+            "archived": true
+        }))).await.inspect_err(|_| error!("Failed to delete result {}", page_id))?;
 
         info!("Successfully deleted result {} for game type: {:?}", page_id, game_type);
         Ok(())
     }
-} 
\ No newline at end of file
+
+    async fn restore_entry(&self, page_id: &str, game_type: GameType) -> Result<SpinResult, Error> {
+        info!("Restoring result {} for game type: {:?}", page_id, game_type);
+        self.get_owned_page(page_id, game_type).await?;
+
+        let page = self.send(self.request(Method::PATCH, &format!("/pages/{}", page_id)).json(&json!({
+            "archived": false
+        }))).await.inspect_err(|_| error!("Failed to restore result {}", page_id))?;
+
+        info!("Successfully restored result {} for game type: {:?}", page_id, game_type);
+        Ok(Self::parse_page(&page, game_type))
+    }
+
+    async fn get_archived_entries(&self, _game_type: GameType) -> Result<Vec<ArchivedResult>, Error> {
+        // Database queries and search both skip archived pages
+        Err(Error::Unsupported("Notion cannot list archived pages".to_string()))
+    }
+
+    async fn record_play(&self, _game_type: GameType, _played_at: DateTime<Utc>) -> Result<(), Error> {
+        // Only wins are stored in Notion; plays are counted by the mirror
+        Err(Error::Unsupported("Notion does not count plays".to_string()))
+    }
+
+    async fn play_counts(&self, _game_type: GameType, _from: Option<DateTime<Utc>>, _to: Option<DateTime<Utc>>) -> Result<Vec<(DateTime<Utc>, u64)>, Error> {
+        Err(Error::Unsupported("Notion does not count plays".to_string()))
+    }
+
+    async fn check_token(&self) -> Result<(), Error> {
+        self.send(self.request(Method::GET, "/users/me")).await?;
+        Ok(())
+    }
+
+    async fn check_database(&self, game_type: GameType) -> Result<(), Error> {
+        let database_id = self.get_database_id(game_type)?;
+        self.check_database_in(database_id).await
+    }
+
+    async fn check_database_in(&self, database_id: &str) -> Result<(), Error> {
+        match self.send(self.request(Method::GET, &format!("/databases/{}", database_id))).await {
+            Ok(_) => Ok(()),
+            // Notion answers 404 for databases that exist but are not shared with the integration
+            Err(Error::NotFound) => Err(Error::NotionApi(format!("Database {} not found or not shared with the integration", database_id))),
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub mod domain;
+pub mod application;
+pub mod infrastructure;
+pub mod api;
+pub mod config;
+pub mod reload;
+pub mod shutdown;
+pub mod metrics;
@@ -1,25 +1,170 @@
-mod domain;
-mod application;
-mod infrastructure;
-mod api;
-
 use dotenv::dotenv;
-use std::env;
-use std::collections::HashMap;
-use infrastructure::notion::NotionClient;
-use application::services::NotionService;
-use tracing::{info, Level};
+use std::net::SocketAddr;
+use std::future::IntoFuture;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::{timeout_at, Instant};
+use notion_crud::config::{Config, ConfigReport};
+use notion_crud::{reload, shutdown};
+use notion_crud::infrastructure::notion::NotionClient;
+use notion_crud::infrastructure::mirror::MirroredRepository;
+use notion_crud::application::services::NotionService;
+use notion_crud::api::auth::ApiKeyStore;
+use notion_crud::api::identity::PlayerTokenVerifier;
+use notion_crud::api::rate_limit::RateLimiter;
+use notion_crud::api::idempotency::IdempotencyStore;
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{FmtSubscriber, EnvFilter};
-use crate::domain::models::GameType;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    init_logging();
+
+    info!("Starting Notion CRUD API server");
+    
+    // Collect every configuration problem before giving up, rather than stopping at the first
+    let (config, mut report) = match Config::load() {
+        Ok(config) => (Some(config), ConfigReport::default()),
+        Err(report) => (None, report),
+    };
+    let api_keys = ApiKeyStore::from_env()
+        .map_err(|err| report.push(format!("admin API keys: {}", err)))
+        .ok();
+    let player_tokens = PlayerTokenVerifier::from_env()
+        .map_err(|err| report.push(format!("player tokens: {}", err)))
+        .ok();
+    let rate_limiter = RateLimiter::from_env()
+        .map_err(|err| report.push(format!("rate limits: {}", err)))
+        .ok();
+
+    // Resources the configuration points at are only checked once it is valid
+    let mirror = config.as_ref().and_then(|config| {
+        open_mirror(config)
+            .map_err(|err| report.push(format!("mirror: {}", err)))
+            .ok()
+    });
+    let listener = match &config {
+        Some(config) => tokio::net::TcpListener::bind(config.bind_addr())
+            .await
+            .map_err(|err| report.push(format!("server.bind: cannot listen on {}: {}", config.bind_addr(), err)))
+            .ok(),
+        None => None,
+    };
+
+    let (Some(config), Some(api_keys), Some(player_tokens), Some(rate_limiter), Some(mirror), Some(listener)) =
+        (config, api_keys, player_tokens, rate_limiter, mirror, listener)
+    else {
+        error!("Invalid configuration, {}", report);
+        std::process::exit(1);
+    };
+
+    mirror.sync_all().await;
+    mirror.spawn_resync(Duration::from_secs(config.mirror.resync_secs), Duration::from_secs(config.mirror.full_sync_secs));
+
+    let notion_service = NotionService::new(mirror, config.game_settings())
+        .with_stats_cache_ttl(Duration::from_secs(config.stats.cache_secs))
+        .with_readiness_cache_ttl(Duration::from_secs(config.health.readiness_cache_secs));
+
+    // Game settings can be reloaded on SIGHUP, when the config file changes or via POST /admin/reload
+    #[cfg(unix)]
+    reload::spawn_sighup_reload(notion_service.clone());
+    if let (Some(path), true) = (Config::file_path(), config.reload.watch_secs > 0) {
+        info!("Watching {} for configuration changes", path.display());
+        reload::spawn_file_watch(notion_service.clone(), path, Duration::from_secs(config.reload.watch_secs));
+    }
     
+    if api_keys.is_empty() {
+        warn!("No admin API keys configured, admin endpoints will reject every request");
+    } else {
+        info!("Loaded {} admin API keys", api_keys.len());
+    }
+    if player_tokens.is_enabled() {
+        info!("Player tokens required for play endpoints");
+    } else {
+        info!("Player tokens disabled, /players endpoints are not served");
+    }
+
+    let idempotency = IdempotencyStore::new(Duration::from_secs(config.idempotency.ttl_secs), config.idempotency.max_entries);
+
+    let app = notion_crud::api::routes::create_router(notion_service.clone(), &config, api_keys, player_tokens, rate_limiter, idempotency);
+
+    // run our app with hyper
+    info!("Server listening on {}", config.bind_addr());
+
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async {
+            let _ = stopped.await;
+        });
+    let mut server = tokio::spawn(server.into_future());
+
+    tokio::select! {
+        result = &mut server => {
+            match result {
+                Ok(Ok(())) => return,
+                Ok(Err(err)) => error!("Server stopped: {}", err),
+                Err(err) => error!("Server task failed: {}", err),
+            }
+            std::process::exit(1);
+        }
+        _ = shutdown::signal() => {}
+    }
+
+    // Stop accepting connections, then give running requests and the Notion
+    // writes behind them until the deadline to finish
+    let drain_timeout = config.drain_timeout();
+    info!("Draining for up to {:?}", drain_timeout);
+    let deadline = Instant::now() + drain_timeout;
+    let _ = stop.send(());
+
+    if timeout_at(deadline, &mut server).await.is_err() {
+        warn!("Requests still running after {:?}, closing them", drain_timeout);
+        server.abort();
+    }
+    // Plays are written in their own tasks, so a closed request has not cancelled them
+    if timeout_at(deadline, notion_service.flush_writes()).await.is_err() {
+        warn!("Plays still being written to Notion after {:?}, they may be lost", drain_timeout);
+    }
+    info!("Shut down");
+}
+
+/// The Notion client behind a local mirror of its databases, optionally persisted to SQLite.
+fn open_mirror(config: &Config) -> Result<MirroredRepository<NotionClient>, String> {
+    let notion_client = NotionClient::new(config.database_ids(), config.notion.api_token.clone())
+        .with_requests_per_second(config.notion.requests_per_second)
+        .with_timeout(config.notion_timeout());
+
+    match &config.mirror.sqlite_path {
+        Some(path) => MirroredRepository::with_sqlite(notion_client, path)
+            .map_err(|err| format!("cannot open {}: {}", path, err)),
+        None => Ok(MirroredRepository::new(notion_client)),
+    }
+}
+
+/// `LOG_FORMAT=json` writes one JSON object per line, with the request span's
+/// fields such as `request_id`, for log aggregators. Otherwise logs are pretty-printed.
+fn init_logging() {
+    let filter = EnvFilter::from_default_env()
+        .add_directive(Level::INFO.into())
+        .add_directive("notion_crud=debug".parse().unwrap());
+    let format = std::env::var("LOG_FORMAT").unwrap_or_default();
+
+    if format.eq_ignore_ascii_case("json") {
+        FmtSubscriber::builder()
+            .with_env_filter(filter)
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_file(true)
+            .with_line_number(true)
+            .init();
+        return;
+    }
+
     FmtSubscriber::builder()
-        .with_env_filter(EnvFilter::from_default_env()
-            .add_directive(Level::INFO.into())
-            .add_directive("notion_crud=debug".parse().unwrap()))
+        .with_env_filter(filter)
         .with_file(true)
         .with_line_number(true)
         .with_thread_ids(true)
@@ -27,56 +172,7 @@ async fn main() {
         .with_target(false)
         .pretty()
         .init();
-
-    info!("Starting Notion CRUD API server");
-    
-    // Read database IDs for each game type
-    let mut database_ids = HashMap::new();
-    
-    // Check for the legacy database ID first (for backward compatibility)
-    if let Ok(legacy_db_id) = env::var("NOTION_DATABASE_ID") {
-        info!("Found legacy database ID, using for all game types");
-        database_ids.insert(GameType::Spin, legacy_db_id.clone());
-        database_ids.insert(GameType::Wheel, legacy_db_id);
-    } else {
-        // Read specific database IDs for each game
-        if let Ok(spin_db_id) = env::var("NOTION_DATABASE_ID_SPIN") {
-            info!("Using dedicated database for spin game");
-            database_ids.insert(GameType::Spin, spin_db_id);
-        } else {
-            panic!("NOTION_DATABASE_ID_SPIN must be set if NOTION_DATABASE_ID is not defined");
-        }
-        
-        if let Ok(wheel_db_id) = env::var("NOTION_DATABASE_ID_WHEEL") {
-            info!("Using dedicated database for wheel game");
-            database_ids.insert(GameType::Wheel, wheel_db_id);
-        } else {
-            panic!("NOTION_DATABASE_ID_WHEEL must be set if NOTION_DATABASE_ID is not defined");
-        }
-        
-        // Additional game types can be added here in the future
+    if !format.is_empty() && !format.eq_ignore_ascii_case("pretty") {
+        warn!("Unknown LOG_FORMAT {:?}, expected json or pretty", format);
     }
-    
-    let api_token = env::var("NOTION_API_TOKEN")
-        .expect("NOTION_API_TOKEN must be set");
-    let daily_spin_limit = env::var("DAILY_SPIN_LIMIT")
-        .unwrap_or_else(|_| "1".to_string())
-        .parse::<i32>()
-        .unwrap_or(1);
-
-    let notion_client = NotionClient::new(database_ids, api_token, daily_spin_limit);
-    let notion_service = NotionService::new(notion_client);
-    
-    let app = api::routes::create_router(notion_service);
-
-    // run our app with hyper
-    let port = env::var("PORT").unwrap_or_else(|_| "80".to_string());
-    let addr = format!("0.0.0.0:{}", port);
-    info!("Server will listen on {}", addr);
-    
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .unwrap();
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+}
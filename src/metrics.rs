@@ -0,0 +1,85 @@
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder};
+use std::sync::LazyLock;
+use std::time::Duration;
+use crate::domain::models::GameType;
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Counters and histograms exposed on `/metrics`, shared by the whole process.
+pub struct Metrics {
+    registry: Registry,
+    plays: IntCounterVec,
+    wins: IntCounterVec,
+    limit_rejections: IntCounterVec,
+    notion_requests: HistogramVec,
+    notion_errors: IntCounterVec,
+    http_requests: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter");
+            registry.register(Box::new(counter.clone())).expect("unique metric name");
+            counter
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str]| {
+            let histogram = HistogramVec::new(HistogramOpts::new(name, help), labels).expect("valid histogram");
+            registry.register(Box::new(histogram.clone())).expect("unique metric name");
+            histogram
+        };
+
+        Self {
+            plays: counter("game_plays_total", "Plays drawn, by game and prize", &["game", "prize"]),
+            wins: counter("game_wins_total", "Winning plays drawn, by game and prize", &["game", "prize"]),
+            limit_rejections: counter("game_limit_rejections_total", "Wins refused because the player reached their play limit", &["game"]),
+            notion_requests: histogram(
+                "notion_request_duration_seconds",
+                "Notion API request latency, by endpoint and response status",
+                &["endpoint", "status"],
+            ),
+            notion_errors: counter("notion_errors_total", "Failed Notion API requests, by Notion error code", &["code"]),
+            http_requests: histogram(
+                "http_request_duration_seconds",
+                "HTTP request latency, by method, route and response status",
+                &["method", "path", "status"],
+            ),
+            registry,
+        }
+    }
+}
+
+pub fn record_play(game_type: GameType, prize: &str, is_win: bool) {
+    let labels = [game_type.as_str(), prize];
+    METRICS.plays.with_label_values(&labels).inc();
+    if is_win {
+        METRICS.wins.with_label_values(&labels).inc();
+    }
+}
+
+pub fn record_limit_rejection(game_type: GameType) {
+    METRICS.limit_rejections.with_label_values(&[game_type.as_str()]).inc();
+}
+
+/// `status` is the HTTP status code, or `error` when no response arrived.
+pub fn observe_notion_request(endpoint: &str, status: &str, elapsed: Duration) {
+    METRICS.notion_requests.with_label_values(&[endpoint, status]).observe(elapsed.as_secs_f64());
+}
+
+pub fn record_notion_error(code: &str) {
+    METRICS.notion_errors.with_label_values(&[code]).inc();
+}
+
+pub fn observe_http_request(method: &str, path: &str, status: u16, elapsed: Duration) {
+    METRICS.http_requests
+        .with_label_values(&[method, path, &status.to_string()])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Every metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    TextEncoder::new()
+        .encode_to_string(&METRICS.registry.gather())
+        .unwrap_or_default()
+}
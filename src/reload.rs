@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+use crate::application::services::NotionService;
+use crate::config::{Config, ConfigReport};
+use crate::domain::repository::NotionRepository;
+
+/// Re-reads the configuration and swaps in its game settings. An invalid
+/// configuration leaves the current settings in place.
+pub fn reload<R: NotionRepository + Clone + 'static>(service: &NotionService<R>, trigger: &str) -> Result<(), ConfigReport> {
+    match Config::load() {
+        Ok(config) => {
+            service.apply_settings(config.game_settings());
+            info!("Reloaded game settings after {}", trigger);
+            Ok(())
+        }
+        Err(report) => {
+            warn!("Keeping current game settings, reload after {} failed: {}", trigger, report);
+            Err(report)
+        }
+    }
+}
+
+/// Reloads whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub fn spawn_sighup_reload<R: NotionRepository + Clone + 'static>(service: NotionService<R>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            warn!("Cannot listen for SIGHUP, reload on signal is disabled: {}", err);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            let _ = reload(&service, "SIGHUP");
+        }
+    });
+}
+
+/// Polls the config file and reloads when its modification time changes.
+pub fn spawn_file_watch<R: NotionRepository + Clone + 'static>(service: NotionService<R>, path: PathBuf, interval: Duration) {
+    let modified = |path: &PathBuf| -> Option<SystemTime> { std::fs::metadata(path).and_then(|meta| meta.modified()).ok() };
+
+    tokio::spawn(async move {
+        let mut last_modified = modified(&path);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let current = modified(&path);
+            if current != last_modified {
+                last_modified = current;
+                let _ = reload(&service, &format!("a change to {}", path.display()));
+            }
+        }
+    });
+}
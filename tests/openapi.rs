@@ -0,0 +1,131 @@
+//! The OpenAPI document and the router built in `api::routes` must list the same operations.
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::{middleware, Router};
+use notion_crud::api::auth::ApiKeyStore;
+use notion_crud::api::identity::PlayerTokenVerifier;
+use notion_crud::api::idempotency::IdempotencyStore;
+use notion_crud::api::openapi::ApiDoc;
+use notion_crud::api::rate_limit::RateLimiter;
+use notion_crud::api::routes::create_router;
+use notion_crud::application::services::NotionService;
+use notion_crud::config::Config;
+use notion_crud::infrastructure::mirror::MirroredRepository;
+use notion_crud::infrastructure::notion::NotionClient;
+use tower::ServiceExt;
+use utoipa::OpenApi;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+/// The Redoc page rendering the document.
+const UNDOCUMENTED: [&str; 1] = ["/docs"];
+
+/// An HTTP method and a path in OpenAPI's `{param}` form.
+type Operation = (String, String);
+
+fn documented() -> BTreeSet<Operation> {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    spec["paths"]
+        .as_object()
+        .expect("paths object")
+        .iter()
+        .flat_map(|(path, item)| {
+            METHODS
+                .iter()
+                .filter(|method| item.get(**method).is_some())
+                .map(|method| (method.to_string(), path.clone()))
+        })
+        .collect()
+}
+
+/// The app router with every route answering 204 before any middleware or
+/// handler runs, so nothing reaches Notion, and unmatched requests answering
+/// 404 or 405 from the router itself.
+fn app() -> Router {
+    // Player routes are only registered when player tokens are configured
+    std::env::set_var("PLAYER_TOKEN_HS256_SECRET", "test-secret");
+    let player_tokens = PlayerTokenVerifier::from_env().expect("player token verifier");
+
+    let config = Config::default();
+    let client = NotionClient::new(HashMap::new(), "token".to_string());
+    let service = NotionService::new(MirroredRepository::new(client), config.game_settings());
+    create_router(
+        service,
+        &config,
+        ApiKeyStore::default(),
+        player_tokens,
+        RateLimiter::default(),
+        IdempotencyStore::new(Duration::from_secs(60), 100),
+    )
+    .route_layer(middleware::from_fn(|_: Request<Body>, _: middleware::Next| async { StatusCode::NO_CONTENT }))
+    .method_not_allowed_fallback(|| async { StatusCode::METHOD_NOT_ALLOWED })
+    .fallback(|| async { StatusCode::NOT_FOUND })
+}
+
+/// Paths registered on `router`, in OpenAPI's `{param}` form. axum has no API to
+/// list routes, so they are read from the router's `Debug` output.
+fn registered_paths(router: &Router) -> BTreeSet<String> {
+    let debug = format!("{:?}", router);
+    let (path_router, _) = debug.split_once("fallback_router").expect("router debug output");
+    path_router
+        .split("): \"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"').map(|(path, _)| path))
+        .map(openapi_path)
+        .filter(|path| !UNDOCUMENTED.contains(&path.as_str()))
+        .collect()
+}
+
+/// Rewrites axum's `:param` segments as `{param}`.
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Fills in `{param}` segments; the values are never parsed as no handler runs.
+fn concrete_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| if segment.starts_with('{') { "x" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+async fn status(router: &Router, method: &str, path: &str) -> StatusCode {
+    let request = Request::builder()
+        .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+        .uri(concrete_path(path))
+        .body(Body::empty())
+        .unwrap();
+    router.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn spec_matches_routes() {
+    let router = app();
+    let documented = documented();
+
+    let mut routed = BTreeSet::new();
+    for path in registered_paths(&router) {
+        for method in METHODS {
+            if status(&router, method, &path).await == StatusCode::NO_CONTENT {
+                routed.insert((method.to_string(), path.clone()));
+            }
+        }
+    }
+    let undocumented: Vec<_> = routed.difference(&documented).collect();
+    let unrouted: Vec<_> = documented.difference(&routed).collect();
+    assert!(
+        undocumented.is_empty() && unrouted.is_empty(),
+        "routes missing from the OpenAPI document: {:?}\ndocumented operations without a route: {:?}",
+        undocumented,
+        unrouted,
+    );
+}